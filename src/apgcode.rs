@@ -0,0 +1,263 @@
+use std::fmt;
+
+use crate::grid::Grid;
+
+// apgcodes as used by Catagolue: a prefix with the object type ("xs" still
+// life with its population, "xp" oscillator or "xq" spaceship with its period)
+// and the extended Wechsler format of the object in its canonical phase and
+// orientation, e.g. "xs4_33" (block) or "xq4_153" (glider).
+
+const WECHSLER_CHARS: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+const STRIP_HEIGHT: i64 = 5;
+const MAX_ZERO_RUN: usize = 39;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApgcodeError {
+    EmptyPattern,
+    Dies(usize),
+    NotPeriodic(usize),
+    UnsupportedPrefix(String),
+    InvalidCharacter(char, usize)
+}
+
+// Treats all live cells of the grid as one object and runs it under the
+// grid's rule for up to max_period generations to find its period.
+pub fn encode(grid: &Grid, max_period: usize) -> Result<String, ApgcodeError> {
+    let start = normalize(grid.get_live_cells());
+    if start.is_empty() {
+        return Err(ApgcodeError::EmptyPattern)
+    }
+
+    let (start_row, start_col, _, _) = grid.get_bounding_box().unwrap();
+    let mut sim = grid.clone();
+    let mut phases = vec![start.clone()];
+    for generation in 1..=max_period {
+        sim.calc_next_generation();
+        let (min_row, min_col, _, _) = match sim.get_bounding_box() {
+            Some(bounds) => bounds,
+            None => return Err(ApgcodeError::Dies(generation))
+        };
+        let cells = normalize(sim.get_live_cells());
+
+        if cells == start {
+            let moved = min_row != start_row || min_col != start_col;
+            let prefix = match (moved, generation) {
+                (true, period) => format!("xq{}", period),
+                (false, 1) => format!("xs{}", start.len()),
+                (false, period) => format!("xp{}", period)
+            };
+            return Ok(format!("{}_{}", prefix, canonical_code(&phases)))
+        }
+        phases.push(cells);
+    }
+
+    return Err(ApgcodeError::NotPeriodic(max_period))
+}
+
+// Returns the cells of the object with its top-left corner at (0, 0).
+pub fn decode(code: &str) -> Result<Vec<(i64, i64)>, ApgcodeError> {
+    let (prefix, wechsler) = match code.find('_') {
+        Some(index) => (&code[..index], &code[index + 1..]),
+        None => return Err(ApgcodeError::UnsupportedPrefix(code.to_owned()))
+    };
+    let valid_prefix = prefix.len() > 2
+        && (prefix.starts_with("xs") || prefix.starts_with("xp") || prefix.starts_with("xq"))
+        && prefix[2..].chars().all(|c| c.is_ascii_digit());
+    if !valid_prefix {
+        return Err(ApgcodeError::UnsupportedPrefix(prefix.to_owned()))
+    }
+
+    let offset = prefix.len() + 1;
+    let mut cells = Vec::new();
+    let (mut row, mut col) = (0i64, 0i64);
+    let mut chars = wechsler.char_indices();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            'z' => {
+                row += STRIP_HEIGHT;
+                col = 0;
+            },
+            'w' => col += 2,
+            'x' => col += 3,
+            'y' => match chars.next() {
+                Some((pos, n)) => match WECHSLER_CHARS.find(n) {
+                    Some(index) => col += 4 + index as i64,
+                    None => return Err(ApgcodeError::InvalidCharacter(n, offset + pos))
+                },
+                None => return Err(ApgcodeError::InvalidCharacter(c, offset + pos))
+            },
+            '0'..='9' | 'a'..='v' => {
+                let value = WECHSLER_CHARS.find(c).unwrap();
+                for bit in 0..STRIP_HEIGHT {
+                    if value & (1 << bit) != 0 {
+                        cells.push((row + bit, col));
+                    }
+                }
+                col += 1;
+            },
+            _ => return Err(ApgcodeError::InvalidCharacter(c, offset + pos))
+        }
+    }
+
+    return Ok(normalize(cells))
+}
+
+// The shortest code over all phases and orientations, ties broken by ASCII order.
+fn canonical_code(phases: &[Vec<(i64, i64)>]) -> String {
+    let mut best: Option<String> = None;
+    for cells in phases {
+        for orientation in 0..8 {
+            let transformed = normalize(cells.iter().map(|cell| orient(*cell, orientation)).collect());
+            let code = wechsler(&transformed);
+            let better = match &best {
+                Some(b) => (code.len(), &code) < (b.len(), b),
+                None => true
+            };
+            if better {
+                best = Some(code);
+            }
+        }
+    }
+    return best.unwrap_or_default()
+}
+
+fn wechsler(cells: &[(i64, i64)]) -> String {
+    let height = cells.iter().map(|c| c.0).max().map_or(0, |r| r + 1);
+    let width = cells.iter().map(|c| c.1).max().map_or(0, |c| c + 1);
+    let chars: Vec<char> = WECHSLER_CHARS.chars().collect();
+
+    let mut code = String::new();
+    for strip in 0..((height + STRIP_HEIGHT - 1) / STRIP_HEIGHT) {
+        if strip != 0 {
+            code.push('z');
+        }
+
+        let mut zeros = 0;
+        for col in 0..width {
+            let mut value = 0;
+            for bit in 0..STRIP_HEIGHT {
+                if cells.binary_search(&(strip * STRIP_HEIGHT + bit, col)).is_ok() {
+                    value |= 1 << bit;
+                }
+            }
+            if value == 0 {
+                zeros += 1;
+                continue;
+            }
+
+            while zeros > 0 {
+                let run = zeros.min(MAX_ZERO_RUN);
+                match run {
+                    1 => code.push('0'),
+                    2 => code.push('w'),
+                    3 => code.push('x'),
+                    _ => {
+                        code.push('y');
+                        code.push(chars[run - 4]);
+                    }
+                }
+                zeros -= run;
+            }
+            code.push(chars[value]);
+        }
+    }
+    return code
+}
+
+fn orient((row, col): (i64, i64), orientation: usize) -> (i64, i64) {
+    let (row, col) = if orientation & 4 != 0 { (col, row) } else { (row, col) };
+    let row = if orientation & 2 != 0 { -row } else { row };
+    let col = if orientation & 1 != 0 { -col } else { col };
+    return (row, col)
+}
+
+// Moves the cells so the bounding box starts at (0, 0) and sorts them.
+fn normalize(mut cells: Vec<(i64, i64)>) -> Vec<(i64, i64)> {
+    let min_row = cells.iter().map(|c| c.0).min().unwrap_or(0);
+    let min_col = cells.iter().map(|c| c.1).min().unwrap_or(0);
+    for cell in cells.iter_mut() {
+        cell.0 -= min_row;
+        cell.1 -= min_col;
+    }
+    cells.sort_unstable();
+    return cells
+}
+
+impl fmt::Display for ApgcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApgcodeError::EmptyPattern => write!(f, "pattern is empty"),
+            ApgcodeError::Dies(generation) => write!(f, "pattern dies in generation {}", generation),
+            ApgcodeError::NotPeriodic(max) => write!(f, "pattern does not repeat within {} generations", max),
+            ApgcodeError::UnsupportedPrefix(prefix) => write!(f, "unsupported apgcode prefix '{}'", prefix),
+            ApgcodeError::InvalidCharacter(c, pos) => write!(f, "invalid character '{}' at position {}", c, pos)
+        }
+    }
+}
+
+impl std::error::Error for ApgcodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(cells: &[(i64, i64)]) -> Grid {
+        let mut grid = Grid::new();
+        for (row, col) in cells {
+            grid.set_cell(*row, *col, true);
+        }
+        return grid
+    }
+
+    fn round_trip(code: &str) -> String {
+        return encode(&grid(&decode(code).unwrap()), 100).unwrap()
+    }
+
+    #[test]
+    fn encodes_still_lifes_oscillators_and_spaceships() {
+        assert_eq!(encode(&grid(&[(5, -3), (5, -2), (6, -3), (6, -2)]), 10), Ok("xs4_33".to_owned()));
+        // Either phase of the blinker gives the same code
+        assert_eq!(encode(&grid(&[(0, 0), (0, 1), (0, 2)]), 10), Ok("xp2_7".to_owned()));
+        assert_eq!(encode(&grid(&[(0, 0), (1, 0), (2, 0)]), 10), Ok("xp2_7".to_owned()));
+        // As do all phases and orientations of the glider
+        let mut glider = grid(&[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        for _ in 0..4 {
+            assert_eq!(encode(&glider, 10), Ok("xq4_153".to_owned()));
+            glider.calc_next_generation();
+        }
+        assert_eq!(encode(&grid(&[(0, 1), (1, 0), (2, 0), (2, 1), (2, 2)]), 10), Ok("xq4_153".to_owned()));
+    }
+
+    #[test]
+    fn decodes_to_cells_at_the_origin() {
+        assert_eq!(decode("xs4_33"), Ok(vec![(0, 0), (0, 1), (1, 0), (1, 1)]));
+        assert_eq!(decode("xp2_7"), Ok(vec![(0, 0), (1, 0), (2, 0)]));
+        assert_eq!(decode("xq4_153"), Ok(vec![(0, 0), (0, 1), (0, 2), (1, 2), (2, 1)]));
+        // Zero runs of 2, 3 and 4 + n columns, and a second strip
+        assert_eq!(decode("xs2_1w1"), Ok(vec![(0, 0), (0, 3)]));
+        assert_eq!(decode("xs2_1x1"), Ok(vec![(0, 0), (0, 4)]));
+        assert_eq!(decode("xs2_1y21"), Ok(vec![(0, 0), (0, 7)]));
+        assert_eq!(decode("xs2_1z1"), Ok(vec![(0, 0), (5, 0)]));
+    }
+
+    #[test]
+    fn round_trips_codes() {
+        for code in ["xs4_33", "xs6_696", "xs5_253", "xp2_7", "xp15_4r4z4r4", "xq4_153", "xq4_6frc"] {
+            assert_eq!(round_trip(code), code);
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(encode(&Grid::new(), 10), Err(ApgcodeError::EmptyPattern));
+        assert_eq!(encode(&grid(&[(0, 0), (0, 1)]), 10), Err(ApgcodeError::Dies(1)));
+        // The R-pentomino takes over a thousand generations to settle
+        assert_eq!(encode(&grid(&[(0, 1), (0, 2), (1, 0), (1, 1), (2, 1)]), 10), Err(ApgcodeError::NotPeriodic(10)));
+
+        assert_eq!(decode("xs4"), Err(ApgcodeError::UnsupportedPrefix("xs4".to_owned())));
+        assert_eq!(decode("yl144_1_16_afb5f"), Err(ApgcodeError::UnsupportedPrefix("yl144".to_owned())));
+        assert_eq!(decode("xs4_3!"), Err(ApgcodeError::InvalidCharacter('!', 5)));
+        assert_eq!(decode("xs4_3y"), Err(ApgcodeError::InvalidCharacter('y', 5)));
+        assert_eq!(decode("xs4_3!").unwrap_err().to_string(), "invalid character '!' at position 5");
+    }
+}
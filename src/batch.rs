@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::cli;
+use crate::explore::shape_hash;
+use crate::grid::{Grid, Rule};
+use crate::image::{gif, png, raster, svg};
+use crate::image::raster::RenderOptions;
+use crate::image::svg::SvgOptions;
+use crate::rle::RLE;
+
+// Runs a pattern without any window and prints one tab separated line of
+// generation, population and bounding box per reported generation.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopCondition {
+    Dies,
+    // The pattern repeats in place, a still life or oscillator
+    Stable,
+    // The pattern repeats anywhere, which includes spaceships
+    Periodic
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOptions {
+    pub pattern: String,
+    pub rule: Option<Rule>,
+    pub generations: usize,
+    pub until: Option<StopCondition>,
+    // Report every n generations, the first and last are always reported
+    pub every: usize,
+    // File the final generation is written to as RLE
+    pub output: Option<String>,
+    // Pictures of the final generation
+    pub png: Option<String>,
+    pub svg: Option<String>,
+    // Animation of the reported generations
+    pub gif: Option<String>,
+    // Pixels along each side of a cell in pictures and animations
+    pub cell_size: usize
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RunResult {
+    pub generation: usize,
+    // The condition that ended the run with the period, 0 for dying patterns
+    pub stopped: Option<(StopCondition, usize)>
+}
+
+#[derive(Debug)]
+pub enum BatchError {
+    Load(String),
+    Io(String, io::Error)
+}
+
+// Hundredths of a second each frame of an animation is shown
+const GIF_DELAY: u16 = 10;
+
+pub fn run_command(options: &RunOptions) -> Result<RunResult, BatchError> {
+    let mut grid = Grid::new();
+    let pattern = cli::load_into(&options.pattern, &mut grid).map_err(BatchError::Load)?;
+    if let Some(rule) = options.rule.clone() {
+        grid.set_rule(rule);
+    }
+    // The animation is rendered afterwards, when the number of generations is known
+    let initial_grid = options.gif.as_ref().map(|_| grid.clone());
+
+    let stdout = io::stdout();
+    let result = run(&mut grid, options, &mut stdout.lock()).map_err(|e| BatchError::Io(String::from("stdout"), e))?;
+
+    if let Some(path) = &options.output {
+        let mut rle = RLE::from_grid(&grid);
+        rle.set_name(pattern.name());
+        rle.add_comment(&format!("Generation {}", result.generation));
+        fs::write(path, rle.to_string()).map_err(|e| BatchError::Io(path.clone(), e))?;
+    }
+
+    let render_options = RenderOptions { cell_size: options.cell_size, ..RenderOptions::default() };
+    let region = grid.get_bounding_box().unwrap_or((0, 0, 0, 0));
+    if let Some(path) = &options.png {
+        let image = raster::render(&grid, region, &render_options);
+        fs::write(path, png::write(&image)).map_err(|e| BatchError::Io(path.clone(), e))?;
+    }
+    if let Some(path) = &options.svg {
+        let svg_options = SvgOptions { cell_size: options.cell_size, ..SvgOptions::default() };
+        fs::write(path, svg::write(&grid, region, &svg_options)).map_err(|e| BatchError::Io(path.clone(), e))?;
+    }
+    if let (Some(path), Some(initial_grid)) = (&options.gif, initial_grid) {
+        let frames = raster::render_generations(&initial_grid, 0, result.generation, options.every, None, &render_options);
+        fs::write(path, gif::write(&frames, GIF_DELAY)).map_err(|e| BatchError::Io(path.clone(), e))?;
+    }
+    return Ok(result)
+}
+
+pub fn run<W: Write>(grid: &mut Grid, options: &RunOptions, out: &mut W) -> io::Result<RunResult> {
+    let every = options.every.max(1);
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    writeln!(out, "generation\tpopulation\tmin_row\tmin_col\tmax_row\tmax_col")?;
+
+    let mut generation = 0;
+    loop {
+        let stopped = options.until.and_then(|condition| check(grid, condition, generation, &mut seen));
+        let last = generation == options.generations || stopped.is_some();
+        if generation % every == 0 || last {
+            write_row(out, grid, generation)?;
+        }
+        if last {
+            return Ok(RunResult { generation, stopped })
+        }
+
+        grid.calc_next_generation();
+        generation += 1;
+    }
+}
+
+fn check(grid: &Grid, condition: StopCondition, generation: usize, seen: &mut HashMap<u64, usize>) -> Option<(StopCondition, usize)> {
+    let hash = match (condition, grid.get_bounding_box()) {
+        (_, None) => return Some((StopCondition::Dies, 0)),
+        (StopCondition::Dies, Some(_)) => return None,
+        (StopCondition::Stable, Some(_)) => shape_hash(grid, 0, 0),
+        (StopCondition::Periodic, Some((min_row, min_col, _, _))) => shape_hash(grid, min_row, min_col)
+    };
+
+    return seen.insert(hash, generation).map(|previous| (condition, generation - previous))
+}
+
+fn write_row<W: Write>(out: &mut W, grid: &Grid, generation: usize) -> io::Result<()> {
+    match grid.get_bounding_box() {
+        Some((min_row, min_col, max_row, max_col)) => {
+            writeln!(out, "{}\t{}\t{}\t{}\t{}\t{}", generation, grid.get_population(), min_row, min_col, max_row, max_col)
+        },
+        None => writeln!(out, "{}\t0\t-\t-\t-\t-", generation)
+    }
+}
+
+impl fmt::Display for StopCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StopCondition::Dies => "dies",
+            StopCondition::Stable => "stable",
+            StopCondition::Periodic => "periodic"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Load(e) => write!(f, "{}", e),
+            BatchError::Io(path, e) => write!(f, "{}: {}", path, e)
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(cells: &[(i64, i64)]) -> Grid {
+        let mut grid = Grid::new();
+        for (row, col) in cells {
+            grid.set_cell(*row, *col, true);
+        }
+        return grid
+    }
+
+    fn glider() -> Grid {
+        return grid(&[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)])
+    }
+
+    fn options(generations: usize, until: Option<StopCondition>, every: usize) -> RunOptions {
+        return RunOptions {
+            pattern: String::new(),
+            rule: None,
+            generations,
+            until,
+            every,
+            output: None,
+            png: None,
+            svg: None,
+            gif: None,
+            cell_size: 1
+        }
+    }
+
+    fn run_lines(grid: &mut Grid, options: &RunOptions) -> (RunResult, Vec<String>) {
+        let mut out = Vec::new();
+        let result = run(grid, options, &mut out).unwrap();
+        let lines = String::from_utf8(out).unwrap().lines().map(String::from).collect();
+        return (result, lines)
+    }
+
+    #[test]
+    fn reports_every_n_generations_and_the_last() {
+        let (result, lines) = run_lines(&mut glider(), &options(10, None, 4));
+        assert_eq!(result, RunResult { generation: 10, stopped: None });
+        assert_eq!(lines, vec![
+            "generation\tpopulation\tmin_row\tmin_col\tmax_row\tmax_col",
+            "0\t5\t0\t0\t2\t2",
+            "4\t5\t1\t1\t3\t3",
+            "8\t5\t2\t2\t4\t4",
+            "10\t5\t3\t2\t5\t4"
+        ]);
+    }
+
+    #[test]
+    fn stops_when_the_pattern_dies() {
+        let (result, lines) = run_lines(&mut grid(&[(0, 0), (0, 1)]), &options(100, Some(StopCondition::Dies), 10));
+        assert_eq!(result, RunResult { generation: 1, stopped: Some((StopCondition::Dies, 0)) });
+        assert_eq!(lines.last().unwrap(), "1\t0\t-\t-\t-\t-");
+
+        // Any condition ends the run when nothing is left
+        let (result, _) = run_lines(&mut Grid::new(), &options(100, Some(StopCondition::Periodic), 1));
+        assert_eq!(result.stopped, Some((StopCondition::Dies, 0)));
+    }
+
+    #[test]
+    fn stops_when_the_pattern_repeats() {
+        let blinker = &[(0, -1), (0, 0), (0, 1)];
+        let (result, _) = run_lines(&mut grid(blinker), &options(100, Some(StopCondition::Stable), 1));
+        assert_eq!(result, RunResult { generation: 2, stopped: Some((StopCondition::Stable, 2)) });
+
+        // A glider only repeats when its position is ignored
+        let (result, _) = run_lines(&mut glider(), &options(100, Some(StopCondition::Stable), 1));
+        assert_eq!(result, RunResult { generation: 100, stopped: None });
+        let (result, _) = run_lines(&mut glider(), &options(100, Some(StopCondition::Periodic), 1));
+        assert_eq!(result, RunResult { generation: 4, stopped: Some((StopCondition::Periodic, 4)) });
+    }
+}
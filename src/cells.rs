@@ -0,0 +1,137 @@
+use std::fmt;
+
+use crate::grid::Rule;
+use crate::rle::RLE;
+
+// Reader and writer for the LifeWiki plaintext format. Lines starting with '!'
+// are comments ("!Name:" and "!Author:" are picked up as metadata), every other
+// line is a row of '.' for dead and 'O' (or '*') for live cells.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCellsError {
+    pub line: usize,
+    pub column: usize,
+    pub text: String
+}
+
+pub fn parse(s: &str) -> Result<RLE, ParseCellsError> {
+    let mut name = String::new();
+    let mut author = String::new();
+    let mut comments: Vec<String> = Vec::new();
+    let mut cells: Vec<(i64, i64)> = Vec::new();
+    let mut width = 0;
+    let mut row: i64 = 0;
+
+    for (line_index, line) in s.lines().enumerate() {
+        if let Some(comment) = line.strip_prefix('!') {
+            if let Some(value) = comment.strip_prefix("Name:") {
+                name = value.trim().to_owned();
+            } else if let Some(value) = comment.strip_prefix("Author:") {
+                author = value.trim().to_owned();
+            } else if !comment.trim().is_empty() {
+                comments.push(comment.trim().to_owned());
+            }
+            continue;
+        }
+
+        let line = line.trim_end();
+        for (col, c) in line.chars().enumerate() {
+            match c {
+                '.' => {},
+                'O' | '*' => cells.push((row, col as i64)),
+                _ => return Err(ParseCellsError {
+                    line: line_index + 1,
+                    column: col + 1,
+                    text: c.to_string()
+                })
+            }
+        }
+        width = width.max(line.chars().count() as i64);
+        row += 1;
+    }
+
+    let mut rle = RLE::from_cells_in(&cells, (0, 0, row - 1, width - 1), &Rule::default());
+    rle.set_name(&name);
+    rle.set_author(&author);
+    for comment in comments {
+        rle.add_comment(&comment);
+    }
+    return Ok(rle)
+}
+
+pub fn write(rle: &RLE) -> String {
+    let mut result = String::new();
+    if !rle.name().is_empty() {
+        result.push_str(&format!("!Name: {}\n", rle.name()));
+    }
+    if !rle.author().is_empty() {
+        result.push_str(&format!("!Author: {}\n", rle.author()));
+    }
+    for comment in rle.comments() {
+        result.push_str(&format!("!{}\n", comment));
+    }
+
+    let (top, left) = rle.origin();
+    let mut rows = vec![vec!['.'; rle.width()]; rle.height()];
+    for (row, col) in rle.get_live_cells() {
+        rows[(row - top) as usize][(col - left) as usize] = 'O';
+    }
+    for row in rows {
+        result.extend(row);
+        result.push('\n');
+    }
+    return result
+}
+
+impl fmt::Display for ParseCellsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: unexpected character '{}'", self.line, self.column, self.text)
+    }
+}
+
+impl std::error::Error for ParseCellsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "!Name: Glider\n!Author: Richard K. Guy\n!The smallest, most common spaceship.\n.O.\n..O\nOOO\n";
+
+    #[test]
+    fn round_trips_cells_and_metadata() {
+        let rle = parse(GLIDER).unwrap();
+        assert_eq!((rle.name(), rle.author()), ("Glider", "Richard K. Guy"));
+        assert_eq!(rle.comments(), ["The smallest, most common spaceship."]);
+        assert_eq!((rle.width(), rle.height()), (3, 3));
+        assert_eq!(rle.get_live_cells(), vec![(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        assert_eq!(write(&rle), GLIDER);
+        assert_eq!(parse(&write(&rle)).unwrap().to_string(), rle.to_string());
+    }
+
+    #[test]
+    fn pads_short_and_empty_rows() {
+        // Trailing dead cells may be left out, '*' is live and blank lines are empty rows
+        let rle = parse("!\nO\n\n..*\n").unwrap();
+        assert!(rle.comments().is_empty());
+        assert_eq!((rle.width(), rle.height()), (3, 3));
+        assert_eq!(rle.get_live_cells(), vec![(0, 0), (2, 2)]);
+        assert_eq!(write(&rle), "O..\n...\n..O\n");
+    }
+
+    #[test]
+    fn writes_relative_to_the_origin() {
+        let mut grid = crate::grid::Grid::new();
+        grid.set_cell(-5, 10, true);
+        grid.set_cell(-4, 12, true);
+        let rle = RLE::from_grid(&grid);
+        assert_eq!(write(&rle), "O..\n..O\n");
+        assert_eq!(parse(&write(&rle)).unwrap().get_live_cells(), vec![(0, 0), (1, 2)]);
+    }
+
+    #[test]
+    fn reports_unexpected_characters() {
+        let error = parse("!Name: x\n..\n.x").unwrap_err();
+        assert_eq!(error, ParseCellsError { line: 3, column: 2, text: "x".to_owned() });
+        assert_eq!(error.to_string(), "line 3, column 2: unexpected character 'x'");
+    }
+}
@@ -0,0 +1,1060 @@
+use std::fmt;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::apgcode;
+use crate::batch::{RunOptions, StopCondition};
+use crate::convert::{ConvertOptions, Paste};
+use crate::explore::{ExploreConfig, ExploreOptions, RuleSet, SortKey};
+use crate::format::{self, Format};
+use crate::grid::{Grid, Region, Rule};
+use crate::image::netpbm::ImportOptions;
+use crate::image::raster::RenderOptions;
+use crate::image::y4m::{Camera, RecordOptions, VideoOptions};
+use crate::index::{PatternIndex, Query, SearchOptions};
+use crate::infer::InferOptions;
+use crate::library::{self, Category, ListOptions};
+use crate::macrocell::Macrocell;
+use crate::rle::{self, ApplyOptions, PasteMode, Placement, ReadRleError, Transform, RLE};
+use crate::search::predecessor::PredecessorOptions;
+
+pub const USAGE: &str = "\
+Usage:
+    yagol [OPTIONS] [PATTERN]
+    yagol convert [OPTIONS] INPUT OUTPUT
+    yagol run [OPTIONS] PATTERN
+    yagol infer [OPTIONS] FILE FILE...
+    yagol predecessor [OPTIONS] PATTERN
+    yagol explore [OPTIONS]
+    yagol search [OPTIONS] DIR
+    yagol video [OPTIONS] PATTERN
+    yagol list [OPTIONS]
+
+PATTERN is a pattern file, the name of a built-in pattern or an apgcode such as xq4_153,
+glider by default.
+    --rule RULE            run the pattern under RULE instead of its own rule
+    --delay MS             wait MS milliseconds between generations (default 50)
+    --gps N                run N generations per second instead
+    --zoom N               show N x N cells (default 100)
+    --size WIDTHxHEIGHT    window size in pixels (default 800x800)
+    --paused               start paused
+    --step N               generations advanced by the N key (default 10)
+    --index DIR            look PATTERN up by name in the pattern files under DIR
+    --paste PATTERN        pattern the P key pastes onto the middle of the view
+    --transform T          rotate or flip the pasted pattern, like convert does
+    --mode MODE            how pasted cells combine with the grid, like convert does
+    -h, --help             show this help
+Keys: space pauses and resumes, S steps one generation, N steps N generations,
+P pastes the --paste pattern, R resets to the loaded pattern, escape quits.
+
+convert reads INPUT in any supported format and writes OUTPUT, - for stdin or stdout.
+    --from FORMAT          input format, detected from the contents by default
+    --to FORMAT            output format, from the extension of OUTPUT by default
+    --generations N        advance N generations before writing
+    --crop R1,C1,R2,C2     keep only rows R1 to R2 and columns C1 to C2
+    --normalize            move the top-left corner to 0,0
+    --paste PATTERN        put PATTERN onto the input before advancing, keeping the input's rule
+    --at ROW,COL           paste with the top-left corner at ROW,COL instead of PATTERN's own origin
+    --center ROW,COL       paste centered on ROW,COL
+    --transform T          rotate or flip the pasted pattern: rot90, rot180, rot270 (clockwise),
+                           flip-h, flip-v, flip-diag or flip-anti
+    --mode MODE            how pasted cells combine with the input: copy (default) overwrites,
+                           or keeps cells live in either, and keeps cells live in both,
+                           xor keeps cells live in exactly one
+    --threshold X          pixels darker than X (0 black to 1 white) become live cells (default 0.5)
+    --scale N              N x N pixels per cell in images (default 1)
+FORMAT is one of rle, cells, life105, life106 or mc. Files ending in .pbm, .pgm, .ppm
+or .pnm are read as Netpbm images and written as PBM.
+
+run simulates PATTERN without a window and prints generation, population and bounding box.
+    --generations N        stop after N generations (default 100)
+    --until CONDITION      stop earlier when the pattern dies, is stable or periodic
+    --every N              print every N generations (default 1)
+    --rule RULE            run the pattern under RULE instead of its own rule
+    --output FILE          write the last generation to FILE as RLE
+    --png FILE             draw the last generation to FILE as PNG
+    --svg FILE             draw the last generation to FILE as SVG
+    --gif FILE             animate the printed generations to FILE as GIF
+    --cell-size N          pixels along each side of a cell in pictures (default 4)
+
+infer prints the range of rules that turn each FILE into the next one. The files
+keep their coordinates, use #P or #R lines to line the generations up.
+    --isotropic            infer isotropic non-totalistic rules instead of outer totalistic ones
+
+predecessor prints a pattern that evolves into PATTERN in one generation as RLE.
+    --margin N             let the predecessor reach N cells past PATTERN (default 2)
+    --max-conflicts N      give up after N solver conflicts
+    --rule RULE            search under RULE instead of the pattern's own rule
+
+explore runs random soups in many life-like rules and writes a tab separated report.
+    --random N             explore N random rules (default 100)
+    --all                  explore every rule without B0
+    --min RULE, --max RULE explore the rules between two totalistic rules
+    --soups N              soups per rule (default 8)
+    --soup-size N          side of the square soups (default 16)
+    --density X            fraction of live cells in a soup (default 0.5)
+    --generations N        give up on a soup after N generations (default 1000)
+    --seed N               seed for the rules and soups (default 1)
+    --sort KEY             rule, behaviour, population, generation or period (default rule)
+    --output FILE          write the report to FILE instead of stdout
+
+search indexes the pattern files under DIR and prints the matching ones as tab separated lines.
+    --name TEXT            name or file name contains TEXT
+    --rule RULE            pattern runs under RULE
+    --apgcode CODE         pattern has the apgcode CODE
+    --classify N           find apgcodes of patterns with a period up to N (default 100 with --apgcode)
+
+video writes PATTERN as an uncompressed YUV4MPEG2 video to stdout, e.g. for ffmpeg -i - out.mp4.
+    --generations N        last generation in the video (default 100)
+    --step N               generations between frames (default 1)
+    --size WIDTHxHEIGHT    frame size in pixels (default 640x480)
+    --fps N                frames per second (default 30)
+    --region R1,C1,R2,C2   show rows R1 to R2 and columns C1 to C2 instead of following the pattern
+    --rule RULE            run the pattern under RULE instead of its own rule
+    --output FILE          write the video to FILE instead of stdout
+
+list prints the built-in patterns with their category, name, author and description.
+    --category CATEGORY    still-life, oscillator, spaceship, gun or methuselah";
+
+pub enum Command {
+    View(ViewOptions),
+    Convert(ConvertOptions),
+    Run(RunOptions),
+    Infer(InferOptions),
+    Predecessor(PredecessorOptions),
+    Explore(ExploreOptions),
+    Search(SearchOptions),
+    Video(RecordOptions),
+    List(ListOptions),
+    Help
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ViewOptions {
+    pub pattern: String,
+    pub rule: Option<Rule>,
+    pub delay: Duration,
+    // Number of cells along each side of the view
+    pub zoom: usize,
+    pub window_size: (u32, u32),
+    pub paused: bool,
+    // Generations advanced at once by the step key
+    pub step: usize,
+    // Directory to look the pattern up in by name
+    pub index: Option<String>,
+    // Pasted centered on the view, whatever placement it has
+    pub paste: Option<Paste>
+}
+
+impl Default for ViewOptions {
+    fn default() -> ViewOptions {
+        ViewOptions {
+            pattern: String::from("glider"),
+            rule: None,
+            delay: Duration::from_millis(50),
+            zoom: 100,
+            window_size: (800, 800),
+            paused: false,
+            step: 10,
+            index: None,
+            paste: None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgsError {
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue { option: String, value: String },
+    MissingArgument(&'static str),
+    UnexpectedArgument(String)
+}
+
+// Parses the arguments without the program name.
+pub fn parse(args: &[String]) -> Result<Command, ArgsError> {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        return Ok(Command::Help)
+    }
+    match args.first().map(|a| a.as_str()) {
+        Some("convert") => parse_convert(&args[1..]),
+        Some("run") => parse_run(&args[1..]),
+        Some("infer") => parse_infer(&args[1..]),
+        Some("predecessor") => parse_predecessor(&args[1..]),
+        Some("explore") => parse_explore(&args[1..]),
+        Some("search") => parse_search(&args[1..]),
+        Some("video") => parse_video(&args[1..]),
+        Some("list") => parse_list(&args[1..]),
+        _ => parse_view(args)
+    }
+}
+
+// Finds the pattern by name among the pattern files under the directory and
+// adds it to the grid.
+pub fn load_indexed_into(dir: &str, pattern: &str, grid: &mut Grid) -> Result<RLE, String> {
+    let index = PatternIndex::scan(Path::new(dir), None).map_err(|e| format!("{}: {}", dir, e))?;
+    let entry = index.find(pattern)?;
+    let rle = entry.load().map_err(|e| format!("{}: {}", entry.path.display(), e))?;
+    rle.apply(grid);
+    return Ok(rle)
+}
+
+// Files up to this size load without progress reports
+const QUIET_FILE_SIZE: u64 = 4 << 20;
+
+// Like load_pattern followed by RLE::apply, but RLE and macrocell files are
+// decoded straight into the grid so huge ones don't have to fit in memory
+// twice. The returned RLE only has the metadata of those files.
+pub fn load_into(pattern: &str, grid: &mut Grid) -> Result<RLE, String> {
+    let from = if Path::new(pattern).is_file() { format::from_extension(pattern) } else { None };
+    if from == Some(Format::Rle) {
+        let file = File::open(pattern).map_err(|e| format!("{}: {}", pattern, e))?;
+        return read_rle_into(pattern, file, grid).map_err(|e| format!("{}: {}", pattern, e))
+    }
+    if from == Some(Format::Macrocell) {
+        let contents = fs::read_to_string(pattern).map_err(|e| format!("{}: {}", pattern, e))?;
+        let macrocell: Macrocell = contents.parse().map_err(|e| format!("{}: macrocell: {}", pattern, e))?;
+        macrocell.apply(grid);
+        let mut rle = RLE::from_states_in(&[], grid.get_bounding_box().unwrap_or((0, 0, -1, -1)), grid.get_rule());
+        for comment in macrocell.comments() {
+            rle.add_comment(comment);
+        }
+        return Ok(rle)
+    }
+
+    let rle = load_pattern(pattern)?;
+    rle.apply(grid);
+    return Ok(rle)
+}
+
+// Decodes RLE into the grid, reporting progress on stderr once more than a
+// few megabytes have been read.
+pub fn read_rle_into<R: io::Read>(name: &str, reader: R, grid: &mut Grid) -> Result<RLE, ReadRleError> {
+    let mut reported = false;
+    let result = rle::read_into(reader, grid, |progress| {
+        if progress.bytes > QUIET_FILE_SIZE {
+            eprint!("\r{}: read {} MB, {} live cells", name, progress.bytes >> 20, progress.live_cells);
+            reported = true;
+        }
+    });
+    if reported {
+        eprintln!();
+    }
+    return result
+}
+
+// Reads the pattern from a file if one exists at that path, otherwise looks it
+// up in the built-in library or decodes it as an apgcode.
+pub fn load_pattern(pattern: &str) -> Result<RLE, String> {
+    if Path::new(pattern).is_file() {
+        let contents = fs::read_to_string(pattern).map_err(|e| format!("{}: {}", pattern, e))?;
+        let result = match format::from_extension(pattern) {
+            Some(from) => format::parse_as(&contents, from),
+            None => format::parse(&contents)
+        };
+        return result.map_err(|e| format!("{}: {}", pattern, e))
+    }
+
+    if let Some(p) = library::get(pattern) {
+        return Ok(p.rle())
+    }
+    // apgcodes start with a prefix like xs4, xp2 or xq4 followed by '_'
+    if pattern.starts_with('x') && pattern.contains('_') {
+        let cells = apgcode::decode(pattern).map_err(|e| format!("{}: {}", pattern, e))?;
+        let mut rle = RLE::from_cells(&cells, &Rule::default());
+        rle.set_name(pattern);
+        return Ok(rle)
+    }
+
+    let names: Vec<&str> = library::all().iter().map(|p| p.id()).collect();
+    return Err(format!("no file or built-in pattern '{}', built-in patterns are: {}", pattern, names.join(", ")))
+}
+
+fn parse_view(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = ViewOptions::default();
+    let mut pattern = None;
+    let mut paste_options = ApplyOptions { adopt_rule: false, ..ApplyOptions::default() };
+    let mut placed = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rule" => {
+                let value = value(arg, args.next())?;
+                options.rule = Some(value.parse().map_err(|_| invalid(arg, value))?);
+            },
+            "--delay" => {
+                let value = value(arg, args.next())?;
+                options.delay = Duration::from_millis(value.parse().map_err(|_| invalid(arg, value))?);
+            },
+            "--gps" => {
+                let value = value(arg, args.next())?;
+                let gps = value.parse::<f64>().map_err(|_| invalid(arg, value))?;
+                options.delay = Duration::try_from_secs_f64(1.0 / gps).map_err(|_| invalid(arg, value))?;
+            },
+            "--zoom" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<usize>() {
+                    Ok(zoom) if zoom > 0 => options.zoom = zoom,
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            "--size" => options.window_size = size(arg, value(arg, args.next())?)?,
+            "--paused" => options.paused = true,
+            "--step" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<usize>() {
+                    Ok(step) if step > 0 => options.step = step,
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            "--index" => options.index = Some(value(arg, args.next())?.to_owned()),
+            "--paste" => options.paste = Some(Paste { pattern: value(arg, args.next())?.to_owned(), options: ApplyOptions::default() }),
+            "--transform" => {
+                paste_options.transform = transform(arg, value(arg, args.next())?)?;
+                placed = true;
+            },
+            "--mode" => {
+                paste_options.mode = paste_mode(arg, value(arg, args.next())?)?;
+                placed = true;
+            },
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ if pattern.is_some() => return Err(ArgsError::UnexpectedArgument(arg.clone())),
+            _ => pattern = Some(arg.clone())
+        }
+    }
+
+    match &mut options.paste {
+        Some(paste) => paste.options = paste_options,
+        None if placed => return Err(ArgsError::MissingArgument("--paste")),
+        None => {}
+    }
+    if let Some(pattern) = pattern {
+        options.pattern = pattern;
+    }
+    return Ok(Command::View(options))
+}
+
+fn parse_convert(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = ConvertOptions {
+        input: String::new(),
+        output: String::new(),
+        from: None,
+        to: None,
+        generations: 0,
+        crop: None,
+        normalize: false,
+        paste: None,
+        image: ImportOptions::default()
+    };
+    let mut positional: Vec<String> = Vec::new();
+    let mut paste_options = ApplyOptions { adopt_rule: false, ..ApplyOptions::default() };
+    let mut placed = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--from" | "--to" => {
+                let value = value(arg, args.next())?;
+                let format = format::from_name(value).ok_or_else(|| invalid(arg, value))?;
+                if arg == "--from" {
+                    options.from = Some(format);
+                } else {
+                    options.to = Some(format);
+                }
+            },
+            "--generations" => {
+                let value = value(arg, args.next())?;
+                options.generations = value.parse().map_err(|_| invalid(arg, value))?;
+            },
+            "--crop" => options.crop = Some(region(arg, value(arg, args.next())?)?),
+            "--normalize" => options.normalize = true,
+            "--threshold" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<f64>() {
+                    Ok(threshold) if (0.0..=1.0).contains(&threshold) => options.image.threshold = threshold,
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            "--scale" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<usize>() {
+                    Ok(scale) if scale > 0 => options.image.scale = scale,
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            "--paste" => options.paste = Some(Paste { pattern: value(arg, args.next())?.to_owned(), options: ApplyOptions::default() }),
+            "--at" | "--center" => {
+                let value = value(arg, args.next())?;
+                let numbers: Vec<i64> = value.split(',').map(|n| n.trim().parse()).collect::<Result<_, _>>().map_err(|_| invalid(arg, value))?;
+                if numbers.len() != 2 {
+                    return Err(invalid(arg, value))
+                }
+                paste_options.placement = if arg == "--at" {
+                    Placement::At(numbers[0], numbers[1])
+                } else {
+                    Placement::CenteredOn(numbers[0], numbers[1])
+                };
+                placed = true;
+            },
+            "--transform" => {
+                paste_options.transform = transform(arg, value(arg, args.next())?)?;
+                placed = true;
+            },
+            "--mode" => {
+                paste_options.mode = paste_mode(arg, value(arg, args.next())?)?;
+                placed = true;
+            },
+            "-" => positional.push(arg.clone()),
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ => positional.push(arg.clone())
+        }
+    }
+
+    match &mut options.paste {
+        Some(paste) => paste.options = paste_options,
+        None if placed => return Err(ArgsError::MissingArgument("--paste")),
+        None => {}
+    }
+    let mut positional = positional.into_iter();
+    options.input = positional.next().ok_or(ArgsError::MissingArgument("INPUT"))?;
+    options.output = positional.next().ok_or(ArgsError::MissingArgument("OUTPUT"))?;
+    if let Some(arg) = positional.next() {
+        return Err(ArgsError::UnexpectedArgument(arg))
+    }
+    return Ok(Command::Convert(options))
+}
+
+fn parse_run(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = RunOptions {
+        pattern: String::new(),
+        rule: None,
+        generations: 100,
+        until: None,
+        every: 1,
+        output: None,
+        png: None,
+        svg: None,
+        gif: None,
+        cell_size: RenderOptions::default().cell_size
+    };
+    let mut pattern = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--generations" | "--every" => {
+                let value = value(arg, args.next())?;
+                let number = value.parse().map_err(|_| invalid(arg, value))?;
+                if arg == "--generations" {
+                    options.generations = number;
+                } else {
+                    options.every = number;
+                }
+            },
+            "--until" => {
+                let value = value(arg, args.next())?;
+                options.until = Some(match value {
+                    "dies" | "dead" => StopCondition::Dies,
+                    "stable" => StopCondition::Stable,
+                    "periodic" => StopCondition::Periodic,
+                    _ => return Err(invalid(arg, value))
+                });
+            },
+            "--rule" => {
+                let value = value(arg, args.next())?;
+                options.rule = Some(value.parse().map_err(|_| invalid(arg, value))?);
+            },
+            "--output" => options.output = Some(value(arg, args.next())?.to_owned()),
+            "--png" => options.png = Some(value(arg, args.next())?.to_owned()),
+            "--svg" => options.svg = Some(value(arg, args.next())?.to_owned()),
+            "--gif" => options.gif = Some(value(arg, args.next())?.to_owned()),
+            "--cell-size" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<usize>() {
+                    Ok(size) if size > 0 => options.cell_size = size,
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ if pattern.is_some() => return Err(ArgsError::UnexpectedArgument(arg.clone())),
+            _ => pattern = Some(arg.clone())
+        }
+    }
+
+    options.pattern = pattern.ok_or(ArgsError::MissingArgument("PATTERN"))?;
+    return Ok(Command::Run(options))
+}
+
+fn parse_infer(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = InferOptions {
+        files: Vec::new(),
+        isotropic: false
+    };
+
+    for arg in args {
+        match arg.as_str() {
+            "--isotropic" => options.isotropic = true,
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ => options.files.push(arg.clone())
+        }
+    }
+
+    match options.files.len() {
+        0 => Err(ArgsError::MissingArgument("FILE")),
+        1 => Err(ArgsError::MissingArgument("second FILE")),
+        _ => Ok(Command::Infer(options))
+    }
+}
+
+fn parse_predecessor(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = PredecessorOptions {
+        pattern: String::new(),
+        rule: None,
+        margin: 2,
+        conflict_limit: None
+    };
+    let mut pattern = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--margin" => {
+                let value = value(arg, args.next())?;
+                options.margin = value.parse().map_err(|_| invalid(arg, value))?;
+            },
+            "--max-conflicts" => {
+                let value = value(arg, args.next())?;
+                options.conflict_limit = Some(value.parse().map_err(|_| invalid(arg, value))?);
+            },
+            "--rule" => {
+                let value = value(arg, args.next())?;
+                options.rule = Some(value.parse().map_err(|_| invalid(arg, value))?);
+            },
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ if pattern.is_some() => return Err(ArgsError::UnexpectedArgument(arg.clone())),
+            _ => pattern = Some(arg.clone())
+        }
+    }
+
+    options.pattern = pattern.ok_or(ArgsError::MissingArgument("PATTERN"))?;
+    return Ok(Command::Predecessor(options))
+}
+
+fn parse_explore(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = ExploreOptions {
+        rules: RuleSet::Random { count: 100, seed: 1 },
+        config: ExploreConfig::default(),
+        sort: SortKey::Rule,
+        output: None
+    };
+    let (mut min, mut max) = (None, None);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--random" => {
+                let value = value(arg, args.next())?;
+                options.rules = RuleSet::Random { count: value.parse().map_err(|_| invalid(arg, value))?, seed: 1 };
+            },
+            "--all" => options.rules = RuleSet::All,
+            "--min" | "--max" => {
+                let value = value(arg, args.next())?;
+                let rule: Rule = value.parse().map_err(|_| invalid(arg, value))?;
+                if !matches!(rule, Rule::Totalistic { .. }) {
+                    return Err(invalid(arg, value))
+                }
+                if arg == "--min" {
+                    min = Some(rule);
+                } else {
+                    max = Some(rule);
+                }
+            },
+            "--soups" | "--generations" => {
+                let value = value(arg, args.next())?;
+                let number = value.parse().map_err(|_| invalid(arg, value))?;
+                if arg == "--soups" {
+                    options.config.soups = number;
+                } else {
+                    options.config.max_generations = number;
+                }
+            },
+            "--soup-size" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<i64>() {
+                    Ok(size) if size > 0 => options.config.soup_size = size,
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            "--density" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<f64>() {
+                    Ok(density) if (0.0..=1.0).contains(&density) => options.config.density = density,
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            "--seed" => {
+                let value = value(arg, args.next())?;
+                options.config.seed = value.parse().map_err(|_| invalid(arg, value))?;
+            },
+            "--sort" => {
+                let value = value(arg, args.next())?;
+                options.sort = match value {
+                    "rule" => SortKey::Rule,
+                    "behaviour" | "behavior" => SortKey::Behaviour,
+                    "population" => SortKey::MeanPopulation,
+                    "generation" => SortKey::MeanGeneration,
+                    "period" => SortKey::MaxPeriod,
+                    _ => return Err(invalid(arg, value))
+                };
+            },
+            "--output" => options.output = Some(value(arg, args.next())?.to_owned()),
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ => return Err(ArgsError::UnexpectedArgument(arg.clone()))
+        }
+    }
+
+    if min.is_some() || max.is_some() {
+        let nothing = Rule::Totalistic { become_alive: Vec::new(), stay_alive: Vec::new() };
+        let everything = Rule::Totalistic { become_alive: (0..9).collect(), stay_alive: (0..9).collect() };
+        options.rules = RuleSet::Range { min: min.unwrap_or(nothing), max: max.unwrap_or(everything) };
+    }
+    if let RuleSet::Random { seed, .. } = &mut options.rules {
+        *seed = options.config.seed;
+    }
+    return Ok(Command::Explore(options))
+}
+
+fn parse_search(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = SearchOptions {
+        dir: String::new(),
+        queries: Vec::new(),
+        max_period: None
+    };
+    let mut dir = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--name" => options.queries.push(Query::Name(value(arg, args.next())?.to_owned())),
+            "--rule" => {
+                let value = value(arg, args.next())?;
+                value.parse::<Rule>().map_err(|_| invalid(arg, value))?;
+                options.queries.push(Query::Rule(value.to_owned()));
+            },
+            "--apgcode" => {
+                options.queries.push(Query::Apgcode(value(arg, args.next())?.to_owned()));
+                options.max_period = options.max_period.or(Some(100));
+            },
+            "--classify" => {
+                let value = value(arg, args.next())?;
+                match value.parse::<usize>() {
+                    Ok(period) if period > 0 => options.max_period = Some(period),
+                    _ => return Err(invalid(arg, value))
+                }
+            },
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ if dir.is_some() => return Err(ArgsError::UnexpectedArgument(arg.clone())),
+            _ => dir = Some(arg.clone())
+        }
+    }
+
+    options.dir = dir.ok_or(ArgsError::MissingArgument("DIR"))?;
+    return Ok(Command::Search(options))
+}
+
+fn parse_video(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = RecordOptions {
+        pattern: String::new(),
+        rule: None,
+        generations: 100,
+        output: None,
+        video: VideoOptions::default()
+    };
+    let mut pattern = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--generations" => {
+                let value = value(arg, args.next())?;
+                options.generations = value.parse().map_err(|_| invalid(arg, value))?;
+            },
+            "--step" | "--fps" => {
+                let value = value(arg, args.next())?;
+                let number = match value.parse::<u32>() {
+                    Ok(number) if number > 0 => number,
+                    _ => return Err(invalid(arg, value))
+                };
+                if arg == "--step" {
+                    options.video.step = number as usize;
+                } else {
+                    options.video.frame_rate = number;
+                }
+            },
+            "--size" => {
+                let (width, height) = size(arg, value(arg, args.next())?)?;
+                options.video.width = width as usize;
+                options.video.height = height as usize;
+            },
+            "--region" => options.video.camera = Camera::Fixed(region(arg, value(arg, args.next())?)?),
+            "--rule" => {
+                let value = value(arg, args.next())?;
+                options.rule = Some(value.parse().map_err(|_| invalid(arg, value))?);
+            },
+            "--output" => options.output = Some(value(arg, args.next())?.to_owned()),
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ if pattern.is_some() => return Err(ArgsError::UnexpectedArgument(arg.clone())),
+            _ => pattern = Some(arg.clone())
+        }
+    }
+
+    options.pattern = pattern.ok_or(ArgsError::MissingArgument("PATTERN"))?;
+    return Ok(Command::Video(options))
+}
+
+fn parse_list(args: &[String]) -> Result<Command, ArgsError> {
+    let mut options = ListOptions { category: None };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--category" => {
+                let value = value(arg, args.next())?;
+                options.category = Some(match value {
+                    "still-life" => Category::StillLife,
+                    "oscillator" => Category::Oscillator,
+                    "spaceship" => Category::Spaceship,
+                    "gun" => Category::Gun,
+                    "methuselah" => Category::Methuselah,
+                    _ => return Err(invalid(arg, value))
+                });
+            },
+            _ if arg.starts_with('-') => return Err(ArgsError::UnknownOption(arg.clone())),
+            _ => return Err(ArgsError::UnexpectedArgument(arg.clone()))
+        }
+    }
+    return Ok(Command::List(options))
+}
+
+fn value<'a>(option: &str, value: Option<&'a String>) -> Result<&'a str, ArgsError> {
+    return value.map(|v| v.as_str()).ok_or_else(|| ArgsError::MissingValue(option.to_owned()))
+}
+
+fn invalid(option: &str, value: &str) -> ArgsError {
+    return ArgsError::InvalidValue { option: option.to_owned(), value: value.to_owned() }
+}
+
+// WIDTHxHEIGHT, both above 0
+fn size(option: &str, value: &str) -> Result<(u32, u32), ArgsError> {
+    let size: Vec<u32> = value.split('x').map(|n| n.trim().parse()).collect::<Result<_, _>>().map_err(|_| invalid(option, value))?;
+    if size.len() != 2 || size[0] == 0 || size[1] == 0 {
+        return Err(invalid(option, value))
+    }
+    return Ok((size[0], size[1]))
+}
+
+// R1,C1,R2,C2 with the first corner at the top left
+fn region(option: &str, value: &str) -> Result<Region, ArgsError> {
+    let numbers: Vec<i64> = value.split(',').map(|n| n.trim().parse()).collect::<Result<_, _>>().map_err(|_| invalid(option, value))?;
+    if numbers.len() != 4 || numbers[0] > numbers[2] || numbers[1] > numbers[3] {
+        return Err(invalid(option, value))
+    }
+    return Ok((numbers[0], numbers[1], numbers[2], numbers[3]))
+}
+
+fn transform(option: &str, value: &str) -> Result<Transform, ArgsError> {
+    match value {
+        "none" => Ok(Transform::Identity),
+        "rot90" => Ok(Transform::Rotate90),
+        "rot180" => Ok(Transform::Rotate180),
+        "rot270" => Ok(Transform::Rotate270),
+        "flip-h" => Ok(Transform::FlipHorizontal),
+        "flip-v" => Ok(Transform::FlipVertical),
+        "flip-diag" => Ok(Transform::FlipDiagonal),
+        "flip-anti" => Ok(Transform::FlipAntiDiagonal),
+        _ => Err(invalid(option, value))
+    }
+}
+
+fn paste_mode(option: &str, value: &str) -> Result<PasteMode, ArgsError> {
+    match value {
+        "copy" => Ok(PasteMode::Copy),
+        "or" => Ok(PasteMode::Or),
+        "and" => Ok(PasteMode::And),
+        "xor" => Ok(PasteMode::Xor),
+        _ => Err(invalid(option, value))
+    }
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::UnknownOption(option) => write!(f, "unknown option '{}'", option),
+            ArgsError::MissingValue(option) => write!(f, "{} needs a value", option),
+            ArgsError::InvalidValue { option, value } => write!(f, "invalid value '{}' for {}", value, option),
+            ArgsError::MissingArgument(name) => write!(f, "missing {}", name),
+            ArgsError::UnexpectedArgument(arg) => write!(f, "unexpected argument '{}'", arg)
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        return line.split_whitespace().map(|arg| arg.to_owned()).collect()
+    }
+
+    fn view(line: &str) -> ViewOptions {
+        match parse(&args(line)) {
+            Ok(Command::View(options)) => options,
+            _ => panic!("'{}' is not a view command", line)
+        }
+    }
+
+    fn error(line: &str) -> ArgsError {
+        return parse(&args(line)).err().expect(line)
+    }
+
+    fn convert(line: &str) -> ConvertOptions {
+        match parse(&args(line)) {
+            Ok(Command::Convert(options)) => options,
+            _ => panic!("'{}' is not a convert command", line)
+        }
+    }
+
+    #[test]
+    fn parses_viewer_paste_options() {
+        let options = view("glider --paste block --transform rot90 --mode xor");
+        let paste = options.paste.unwrap();
+        assert_eq!(paste.pattern, "block");
+        assert_eq!(paste.options.transform, Transform::Rotate90);
+        assert_eq!(paste.options.mode, PasteMode::Xor);
+        assert!(!paste.options.adopt_rule);
+
+        assert_eq!(view("glider").paste, None);
+        assert_eq!(error("--mode or"), ArgsError::MissingArgument("--paste"));
+        assert_eq!(error("--paste block --transform spin"), invalid("--transform", "spin"));
+        assert_eq!(error("--paste"), ArgsError::MissingValue("--paste".to_owned()));
+    }
+
+    #[test]
+    fn parses_convert_options() {
+        let options = convert("convert in.rle out.cells");
+        assert_eq!((options.input.as_str(), options.output.as_str()), ("in.rle", "out.cells"));
+        assert_eq!((options.from, options.to, options.generations, options.crop, options.normalize), (None, None, 0, None, false));
+        assert_eq!(options.paste, None);
+        assert_eq!(options.image, ImportOptions::default());
+
+        let options = convert("convert --from LIFE106 --to mc --generations 30 --crop -5,-5,5,5 --normalize - -");
+        assert_eq!((options.input.as_str(), options.output.as_str()), ("-", "-"));
+        assert_eq!((options.from, options.to), (Some(Format::Life106), Some(Format::Macrocell)));
+        assert_eq!((options.generations, options.crop, options.normalize), (30, Some((-5, -5, 5, 5)), true));
+
+        let options = convert("convert scan.pgm out.rle --threshold 0.25 --scale 4");
+        assert_eq!(options.image, ImportOptions { threshold: 0.25, scale: 4 });
+    }
+
+    #[test]
+    fn parses_convert_paste_options() {
+        let paste = convert("convert in.rle out.rle --paste glider --center 10,-3 --transform flip-h --mode and").paste.unwrap();
+        assert_eq!(paste.pattern, "glider");
+        assert_eq!(paste.options, ApplyOptions {
+            placement: Placement::CenteredOn(10, -3),
+            transform: Transform::FlipHorizontal,
+            mode: PasteMode::And,
+            adopt_rule: false
+        });
+        let paste = convert("convert in.rle out.rle --at 1,2 --paste block").paste.unwrap();
+        assert_eq!(paste.options.placement, Placement::At(1, 2));
+        assert_eq!(convert("convert in.rle out.rle --paste block").paste.unwrap().options.placement, ApplyOptions::default().placement);
+    }
+
+    #[test]
+    fn rejects_bad_convert_arguments() {
+        assert_eq!(error("convert"), ArgsError::MissingArgument("INPUT"));
+        assert_eq!(error("convert in.rle"), ArgsError::MissingArgument("OUTPUT"));
+        assert_eq!(error("convert a b c"), ArgsError::UnexpectedArgument("c".to_owned()));
+        assert_eq!(error("convert --to gif a b"), invalid("--to", "gif"));
+        assert_eq!(error("convert --generations -1 a b"), invalid("--generations", "-1"));
+        assert_eq!(error("convert --crop 1,2,3 a b"), invalid("--crop", "1,2,3"));
+        assert_eq!(error("convert --threshold 2 a b"), invalid("--threshold", "2"));
+        assert_eq!(error("convert --scale 0 a b"), invalid("--scale", "0"));
+        assert_eq!(error("convert --at 1 --paste g a b"), invalid("--at", "1"));
+        assert_eq!(error("convert --center 1,2 a b"), ArgsError::MissingArgument("--paste"));
+        assert_eq!(error("convert --mode nand --paste g a b"), invalid("--mode", "nand"));
+        assert_eq!(error("convert a b --from"), ArgsError::MissingValue("--from".to_owned()));
+        assert_eq!(error("convert --fast a b"), ArgsError::UnknownOption("--fast".to_owned()));
+    }
+
+    #[test]
+    fn parses_viewer_options() {
+        assert_eq!(view(""), ViewOptions::default());
+        let options = view("pulsar --rule B36/S23 --delay 20 --zoom 40 --size 1024x768 --paused --step 5 --index patterns");
+        assert_eq!(options, ViewOptions {
+            pattern: "pulsar".to_owned(),
+            rule: Some("B36/S23".parse().unwrap()),
+            delay: Duration::from_millis(20),
+            zoom: 40,
+            window_size: (1024, 768),
+            paused: true,
+            step: 5,
+            index: Some("patterns".to_owned()),
+            paste: None
+        });
+        assert_eq!(view("--gps 4").delay, Duration::from_millis(250));
+    }
+
+    #[test]
+    fn rejects_bad_viewer_arguments() {
+        assert_eq!(error("--rule B9/S"), invalid("--rule", "B9/S"));
+        assert_eq!(error("--delay soon"), invalid("--delay", "soon"));
+        assert_eq!(error("--gps 0"), invalid("--gps", "0"));
+        assert_eq!(error("--zoom 0"), invalid("--zoom", "0"));
+        assert_eq!(error("--size 800"), invalid("--size", "800"));
+        assert_eq!(error("--size 0x600"), invalid("--size", "0x600"));
+        assert_eq!(error("--step 0"), invalid("--step", "0"));
+        assert_eq!(error("glider --zoom"), ArgsError::MissingValue("--zoom".to_owned()));
+        assert_eq!(error("glider lwss"), ArgsError::UnexpectedArgument("lwss".to_owned()));
+        assert_eq!(error("--fullscreen"), ArgsError::UnknownOption("--fullscreen".to_owned()));
+        assert_eq!(error("--fullscreen").to_string(), "unknown option '--fullscreen'");
+    }
+
+    #[test]
+    fn parses_help_anywhere() {
+        for line in ["-h", "--help", "glider --zoom 10 --help", "convert a b -h", "list --help"] {
+            assert!(matches!(parse(&args(line)), Ok(Command::Help)), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parses_run_options() {
+        let options = match parse(&args("run diehard --generations 200 --until dies --every 10 --rule B3/S23 --output out.rle --png a.png --svg a.svg --gif a.gif --cell-size 2")) {
+            Ok(Command::Run(options)) => options,
+            _ => panic!("not a run command")
+        };
+        assert_eq!((options.pattern.as_str(), options.generations, options.until, options.every), ("diehard", 200, Some(StopCondition::Dies), 10));
+        assert_eq!((options.rule, options.output.as_deref()), (Some(Rule::default()), Some("out.rle")));
+        assert_eq!((options.png.as_deref(), options.svg.as_deref(), options.gif.as_deref(), options.cell_size), (Some("a.png"), Some("a.svg"), Some("a.gif"), 2));
+
+        assert_eq!(error("run"), ArgsError::MissingArgument("PATTERN"));
+        assert_eq!(error("run glider --until never"), invalid("--until", "never"));
+        assert_eq!(error("run glider --cell-size 0"), invalid("--cell-size", "0"));
+        assert_eq!(error("run glider lwss"), ArgsError::UnexpectedArgument("lwss".to_owned()));
+    }
+
+    #[test]
+    fn parses_infer_and_predecessor_options() {
+        match parse(&args("infer --isotropic a.rle b.rle c.rle")) {
+            Ok(Command::Infer(options)) => assert_eq!(options, InferOptions { files: args("a.rle b.rle c.rle"), isotropic: true }),
+            _ => panic!("not an infer command")
+        }
+        assert_eq!(error("infer"), ArgsError::MissingArgument("FILE"));
+        assert_eq!(error("infer a.rle"), ArgsError::MissingArgument("second FILE"));
+        assert_eq!(error("infer --totalistic a b"), ArgsError::UnknownOption("--totalistic".to_owned()));
+
+        match parse(&args("predecessor glider --margin 3 --max-conflicts 1000 --rule B36/S23")) {
+            Ok(Command::Predecessor(options)) => assert_eq!(options, PredecessorOptions {
+                pattern: "glider".to_owned(),
+                rule: Some("B36/S23".parse().unwrap()),
+                margin: 3,
+                conflict_limit: Some(1000)
+            }),
+            _ => panic!("not a predecessor command")
+        }
+        assert_eq!(error("predecessor"), ArgsError::MissingArgument("PATTERN"));
+        assert_eq!(error("predecessor glider --margin -1"), invalid("--margin", "-1"));
+    }
+
+    #[test]
+    fn parses_explore_options() {
+        let explore = |line: &str| match parse(&args(line)) {
+            Ok(Command::Explore(options)) => options,
+            _ => panic!("'{}' is not an explore command", line)
+        };
+        let options = explore("explore --random 5 --seed 9 --soups 3 --soup-size 10 --density 0.25 --generations 50 --sort period --output r.tsv");
+        assert!(matches!(options.rules, RuleSet::Random { count: 5, seed: 9 }));
+        assert_eq!((options.config.soups, options.config.soup_size, options.config.density), (3, 10, 0.25));
+        assert_eq!((options.config.max_generations, options.config.seed), (50, 9));
+        assert_eq!((options.sort, options.output.as_deref()), (SortKey::MaxPeriod, Some("r.tsv")));
+
+        assert!(matches!(explore("explore --all").rules, RuleSet::All));
+        match explore("explore --min B3/S23").rules {
+            RuleSet::Range { min, max } => {
+                assert_eq!(min, Rule::default());
+                assert_eq!(max.to_string(), "B012345678/S012345678");
+            },
+            _ => panic!("not a range")
+        }
+
+        assert_eq!(error("explore --max B2a/S"), invalid("--max", "B2a/S"));
+        assert_eq!(error("explore --density 2"), invalid("--density", "2"));
+        assert_eq!(error("explore --soup-size 0"), invalid("--soup-size", "0"));
+        assert_eq!(error("explore --sort speed"), invalid("--sort", "speed"));
+        assert_eq!(error("explore B3/S23"), ArgsError::UnexpectedArgument("B3/S23".to_owned()));
+    }
+
+    #[test]
+    fn parses_search_options() {
+        let search = |line: &str| match parse(&args(line)) {
+            Ok(Command::Search(options)) => options,
+            _ => panic!("'{}' is not a search command", line)
+        };
+        let options = search("search patterns --name glider --rule b3/s23");
+        assert_eq!(options, SearchOptions {
+            dir: "patterns".to_owned(),
+            queries: vec![Query::Name("glider".to_owned()), Query::Rule("b3/s23".to_owned())],
+            max_period: None
+        });
+        // Searching by apgcode classifies patterns unless told how far
+        assert_eq!(search("search patterns --apgcode xs4_33").max_period, Some(100));
+        assert_eq!(search("search patterns --classify 8 --apgcode xs4_33").max_period, Some(8));
+
+        assert_eq!(error("search"), ArgsError::MissingArgument("DIR"));
+        assert_eq!(error("search patterns --rule life2"), invalid("--rule", "life2"));
+        assert_eq!(error("search patterns --classify 0"), invalid("--classify", "0"));
+        assert_eq!(error("search a b"), ArgsError::UnexpectedArgument("b".to_owned()));
+    }
+
+    #[test]
+    fn parses_video_options() {
+        let options = match parse(&args("video glider --generations 40 --step 2 --size 320x240 --fps 24 --region 0,0,9,9 --rule B3/S23 --output out.y4m")) {
+            Ok(Command::Video(options)) => options,
+            _ => panic!("not a video command")
+        };
+        assert_eq!((options.pattern.as_str(), options.generations, options.output.as_deref()), ("glider", 40, Some("out.y4m")));
+        assert_eq!(options.rule, Some(Rule::default()));
+        assert_eq!(options.video, VideoOptions {
+            width: 320,
+            height: 240,
+            frame_rate: 24,
+            step: 2,
+            camera: Camera::Fixed((0, 0, 9, 9)),
+            ..VideoOptions::default()
+        });
+
+        assert_eq!(error("video"), ArgsError::MissingArgument("PATTERN"));
+        assert_eq!(error("video glider --fps 0"), invalid("--fps", "0"));
+        assert_eq!(error("video glider --region 9,9,0,0"), invalid("--region", "9,9,0,0"));
+    }
+
+    #[test]
+    fn parses_list_categories_by_their_printed_name() {
+        for category in [Category::StillLife, Category::Oscillator, Category::Spaceship, Category::Gun, Category::Methuselah] {
+            match parse(&args(&format!("list --category {}", category))) {
+                Ok(Command::List(options)) => assert_eq!(options.category, Some(category)),
+                _ => panic!("'{}' is not a category", category)
+            }
+        }
+        assert!(matches!(parse(&args("list")), Ok(Command::List(ListOptions { category: None }))));
+        assert_eq!(error("list --category still"), invalid("--category", "still"));
+        assert_eq!(error("list glider"), ArgsError::UnexpectedArgument("glider".to_owned()));
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::cli;
+use crate::format::{self, Format, ParsePatternError};
+use crate::grid::{Grid, Region};
+use crate::image::netpbm::{self, ImportOptions, ParseNetpbmError};
+use crate::rle::{ApplyOptions, ReadRleError, RLE};
+
+// Reads a pattern in any supported format and writes it in another, with
+// optional simulation, cropping and normalizing in between. "-" stands for
+// stdin or stdout.
+
+// Netpbm images are recognized by extension, PBM is written for all of them
+const IMAGE_EXTENSIONS: [&str; 4] = ["pbm", "pgm", "ppm", "pnm"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertOptions {
+    pub input: String,
+    pub output: String,
+    // Detected from the contents when not given
+    pub from: Option<Format>,
+    // Taken from the output's extension when not given, RLE otherwise
+    pub to: Option<Format>,
+    pub generations: usize,
+    // Region to keep, after advancing
+    pub crop: Option<Region>,
+    // Moves the top-left corner to (0, 0)
+    pub normalize: bool,
+    pub paste: Option<Paste>,
+    // Threshold and pixels per cell of Netpbm images
+    pub image: ImportOptions
+}
+
+// Another pattern put onto the input before advancing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Paste {
+    pub pattern: String,
+    pub options: ApplyOptions
+}
+
+#[derive(Debug)]
+pub enum ConvertError {
+    Io(String, io::Error),
+    Parse(ParsePatternError),
+    ReadRle(String, ReadRleError),
+    Image(String, ParseNetpbmError),
+    Load(String)
+}
+
+pub fn run(options: &ConvertOptions) -> Result<(), ConvertError> {
+    let paste = match &options.paste {
+        Some(paste) => Some((cli::load_pattern(&paste.pattern).map_err(ConvertError::Load)?, paste.options)),
+        None => None
+    };
+
+    let rle_input = options.from.or_else(|| format::from_extension(&options.input)) == Some(Format::Rle);
+    let rle = if is_image(&options.input) {
+        let data = fs::read(&options.input).map_err(|e| ConvertError::Io(options.input.clone(), e))?;
+        let rle = netpbm::parse(&data, &options.image).map_err(|e| ConvertError::Image(options.input.clone(), e))?;
+        convert(rle, &paste, options)
+    } else if rle_input && needs_grid(options) {
+        // The runs are thrown away anyway, so big RLE files go straight to the grid
+        let mut grid = Grid::new();
+        let rle = read_rle_input(&options.input, &mut grid)?;
+        from_grid(&mut grid, &rle, &paste, options)
+    } else {
+        let input = read_input(&options.input)?;
+        let rle = match options.from {
+            Some(from) => format::parse_as(&input, from)?,
+            None => format::parse(&input)?
+        };
+        convert(rle, &paste, options)
+    };
+
+    if options.to.is_none() && is_image(&options.output) {
+        return write_output(&options.output, &netpbm::write_pbm(&rle, options.image.scale, false))
+    }
+    let to = options.to.unwrap_or_else(|| output_format(&options.output));
+    write_output(&options.output, format::write(&rle, to).as_bytes())
+}
+
+// paste is the loaded pattern of options.paste.
+pub fn convert(rle: RLE, paste: &Option<(RLE, ApplyOptions)>, options: &ConvertOptions) -> RLE {
+    if !needs_grid(options) {
+        let mut result = rle;
+        if options.normalize {
+            result.set_origin((0, 0));
+        }
+        return result
+    }
+
+    let mut grid = Grid::new();
+    rle.apply(&mut grid);
+    return from_grid(&mut grid, &rle, paste, options)
+}
+
+fn needs_grid(options: &ConvertOptions) -> bool {
+    return options.generations > 0 || options.crop.is_some() || options.paste.is_some()
+}
+
+// Pastes, advances and crops the pattern in the grid, keeping the metadata of rle.
+fn from_grid(grid: &mut Grid, rle: &RLE, paste: &Option<(RLE, ApplyOptions)>, options: &ConvertOptions) -> RLE {
+    if let Some((pattern, apply_options)) = paste {
+        pattern.apply_with(grid, apply_options);
+    }
+    for _ in 0..options.generations {
+        grid.calc_next_generation();
+    }
+
+    let mut result = match options.crop {
+        Some(region) => RLE::from_region(grid, region),
+        None => RLE::from_grid(grid)
+    };
+    result.set_name(rle.name());
+    result.set_author(rle.author());
+    for comment in rle.comments() {
+        result.add_comment(comment);
+    }
+    if options.normalize {
+        result.set_origin((0, 0));
+    }
+    return result
+}
+
+// Life 1.05 and 1.06 share .lif, the list of cells is the safer choice.
+fn output_format(path: &str) -> Format {
+    if path.to_ascii_lowercase().ends_with(".lif") {
+        return Format::Life106
+    }
+    return format::from_extension(path).unwrap_or(Format::Rle)
+}
+
+fn is_image(path: &str) -> bool {
+    let extension = Path::new(path).extension().map_or(String::new(), |e| e.to_string_lossy().to_ascii_lowercase());
+    return IMAGE_EXTENSIONS.contains(&extension.as_str())
+}
+
+fn read_input(path: &str) -> Result<String, ConvertError> {
+    let result = if path == "-" {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input).map(|_| input)
+    } else {
+        fs::read_to_string(path)
+    };
+    return result.map_err(|e| ConvertError::Io(path.to_owned(), e))
+}
+
+fn read_rle_input(path: &str, grid: &mut Grid) -> Result<RLE, ConvertError> {
+    let result = if path == "-" {
+        cli::read_rle_into("stdin", io::stdin(), grid)
+    } else {
+        let file = File::open(path).map_err(|e| ConvertError::Io(path.to_owned(), e))?;
+        cli::read_rle_into(path, file, grid)
+    };
+    return result.map_err(|e| ConvertError::ReadRle(path.to_owned(), e))
+}
+
+fn write_output(path: &str, contents: &[u8]) -> Result<(), ConvertError> {
+    let result = if path == "-" {
+        io::stdout().write_all(contents)
+    } else {
+        fs::write(path, contents)
+    };
+    return result.map_err(|e| ConvertError::Io(path.to_owned(), e))
+}
+
+impl From<ParsePatternError> for ConvertError {
+    fn from(error: ParsePatternError) -> ConvertError {
+        ConvertError::Parse(error)
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::Io(path, e) => write!(f, "{}: {}", path, e),
+            ConvertError::Parse(e) => write!(f, "{}", e),
+            ConvertError::ReadRle(path, e) => write!(f, "{}: RLE: {}", path, e),
+            ConvertError::Image(path, e) => write!(f, "{}: {}", path, e),
+            ConvertError::Load(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
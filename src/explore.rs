@@ -0,0 +1,463 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+
+use crate::grid::{Grid, Rule};
+
+// Life-like rules are numbered with birth counts in bits 0-8 and survival
+// counts in bits 9-17. Rules with B0 are skipped everywhere because the grid
+// only evaluates cells next to live ones and cannot simulate them.
+pub const RULE_COUNT: u32 = 1 << 18;
+
+pub enum RuleSet {
+    All,
+    Random { count: usize, seed: u64 },
+    // Every rule that contains all transitions of min and only transitions of max
+    Range { min: Rule, max: Rule }
+}
+
+pub struct ExploreConfig {
+    pub soups: usize,
+    pub soup_size: i64,
+    pub density: f64,
+    pub seed: u64,
+    pub max_generations: usize,
+    pub max_population: usize,
+    // Clusters of live cells are checked for spaceships whenever the bounding
+    // box grows to twice the size of the last check, starting at this size
+    pub spaceship_extent: i64
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Behaviour {
+    Dies,
+    Stabilizes,
+    Spaceships,
+    Chaotic,
+    Explosive
+}
+
+pub struct SoupResult {
+    pub behaviour: Behaviour,
+    pub population: usize,
+    // Generation at which the soup died, repeated or was given up on
+    pub generation: usize,
+    pub period: usize
+}
+
+pub struct RuleSummary {
+    pub rule: Rule,
+    pub behaviour: Behaviour,
+    pub counts: HashMap<Behaviour, usize>,
+    pub mean_population: f64,
+    pub mean_generation: f64,
+    pub max_period: usize
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SortKey {
+    Rule,
+    Behaviour,
+    MeanPopulation,
+    MeanGeneration,
+    MaxPeriod
+}
+
+pub struct ExploreReport {
+    pub summaries: Vec<RuleSummary>
+}
+
+pub struct ExploreOptions {
+    pub rules: RuleSet,
+    pub config: ExploreConfig,
+    pub sort: SortKey,
+    // Written to stdout when not given
+    pub output: Option<String>
+}
+
+const BEHAVIOURS: [Behaviour; 5] = [Behaviour::Dies, Behaviour::Stabilizes, Behaviour::Spaceships, Behaviour::Chaotic, Behaviour::Explosive];
+
+// Longest period a cluster is run for when looking for spaceships
+const MAX_SPACESHIP_PERIOD: usize = 64;
+
+impl Default for ExploreConfig {
+    fn default() -> ExploreConfig {
+        ExploreConfig {
+            soups: 8,
+            soup_size: 16,
+            density: 0.5,
+            seed: 1,
+            max_generations: 1000,
+            max_population: 4096,
+            spaceship_extent: 128
+        }
+    }
+}
+
+pub fn run_command(options: &ExploreOptions) -> Result<(), String> {
+    let mut report = explore(&options.rules, &options.config);
+    report.sort_by(options.sort);
+
+    let result = match &options.output {
+        Some(path) => fs::File::create(path).and_then(|file| {
+            let mut out = io::BufWriter::new(file);
+            report.write_tsv(&mut out)?;
+            out.flush()
+        }),
+        None => report.write_tsv(&mut io::stdout().lock())
+    };
+    let path = options.output.as_deref().unwrap_or("stdout");
+    return result.map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn explore(rules: &RuleSet, config: &ExploreConfig) -> ExploreReport {
+    let mut summaries = Vec::new();
+    for rule in rule_numbers(rules) {
+        summaries.push(summarize_rule(rule_from_number(rule), config));
+    }
+    return ExploreReport { summaries }
+}
+
+pub fn summarize_rule(rule: Rule, config: &ExploreConfig) -> RuleSummary {
+    let mut random = Random::new(soup_seed(config.seed, &rule));
+    let mut counts = HashMap::new();
+    let mut population = 0;
+    let mut generation = 0;
+    let mut max_period = 0;
+
+    for _ in 0..config.soups {
+        let result = run_soup(&rule, config, &mut random);
+        *counts.entry(result.behaviour).or_insert(0) += 1;
+        population += result.population;
+        generation += result.generation;
+        max_period = max_period.max(result.period);
+    }
+
+    // The most common behaviour, ties go to the wilder one
+    let behaviour = counts.iter()
+        .max_by_key(|(behaviour, count)| (**count, **behaviour))
+        .map_or(Behaviour::Dies, |(behaviour, _)| *behaviour);
+    let soups = config.soups.max(1) as f64;
+    return RuleSummary {
+        rule,
+        behaviour,
+        counts,
+        mean_population: population as f64 / soups,
+        mean_generation: generation as f64 / soups,
+        max_period
+    }
+}
+
+pub fn run_soup(rule: &Rule, config: &ExploreConfig, random: &mut Random) -> SoupResult {
+    let mut grid = Grid::new();
+    grid.set_rule(rule.clone());
+    for row in 0..config.soup_size {
+        for col in 0..config.soup_size {
+            if random.next_f64() < config.density {
+                grid.set_cell(row, col, true);
+            }
+        }
+    }
+    return classify(grid, config)
+}
+
+// Runs the grid until it dies, repeats, explodes or gives up after
+// max_generations. Patterns that keep changing as a whole have spaceships
+// when one of their clusters moves on its own.
+pub fn classify(mut grid: Grid, config: &ExploreConfig) -> SoupResult {
+    // Shape of every generation seen so far, mapped to its generation and offset
+    let mut history: HashMap<u64, (usize, i64, i64)> = HashMap::new();
+    let mut next_check = config.spaceship_extent;
+    for generation in 0..config.max_generations {
+        let population = grid.get_population();
+        let (min_row, min_col, max_row, max_col) = match grid.get_bounding_box() {
+            Some(bounds) => bounds,
+            None => return SoupResult { behaviour: Behaviour::Dies, population: 0, generation, period: 0 }
+        };
+        if population > config.max_population {
+            return SoupResult { behaviour: Behaviour::Explosive, population, generation, period: 0 }
+        }
+
+        let shape = shape_hash(&grid, min_row, min_col);
+        if let Some((previous, row, col)) = history.insert(shape, (generation, min_row, min_col)) {
+            let behaviour = if row == min_row && col == min_col { Behaviour::Stabilizes } else { Behaviour::Spaceships };
+            return SoupResult { behaviour, population, generation, period: generation - previous }
+        }
+        let extent = (max_row - min_row).max(max_col - min_col);
+        if extent >= next_check {
+            if let Some(period) = find_spaceship(&grid, config.max_population) {
+                return SoupResult { behaviour: Behaviour::Spaceships, population, generation, period }
+            }
+            next_check = extent.max(1) * 2;
+        }
+
+        grid.calc_next_generation();
+    }
+
+    return SoupResult {
+        behaviour: Behaviour::Chaotic,
+        population: grid.get_population(),
+        generation: config.max_generations,
+        period: 0
+    }
+}
+
+impl ExploreReport {
+    pub fn sort_by(&mut self, key: SortKey) {
+        match key {
+            SortKey::Rule => self.summaries.sort_by_key(|s| rule_number(&s.rule)),
+            SortKey::Behaviour => self.summaries.sort_by_key(|s| s.behaviour),
+            SortKey::MeanPopulation => self.summaries.sort_by(|a, b| a.mean_population.partial_cmp(&b.mean_population).unwrap_or(Ordering::Equal)),
+            SortKey::MeanGeneration => self.summaries.sort_by(|a, b| a.mean_generation.partial_cmp(&b.mean_generation).unwrap_or(Ordering::Equal)),
+            SortKey::MaxPeriod => self.summaries.sort_by_key(|s| s.max_period)
+        }
+    }
+
+    pub fn write_tsv<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "rule\tbehaviour")?;
+        for behaviour in BEHAVIOURS.iter() {
+            write!(out, "\t{}", behaviour)?;
+        }
+        writeln!(out, "\tmean_population\tmean_generation\tmax_period")?;
+
+        for summary in &self.summaries {
+            write!(out, "{}\t{}", summary.rule, summary.behaviour)?;
+            for behaviour in BEHAVIOURS.iter() {
+                write!(out, "\t{}", summary.counts.get(behaviour).unwrap_or(&0))?;
+            }
+            writeln!(out, "\t{:.1}\t{:.1}\t{}", summary.mean_population, summary.mean_generation, summary.max_period)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Behaviour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Behaviour::Dies => "dies",
+            Behaviour::Stabilizes => "stabilizes",
+            Behaviour::Spaceships => "spaceships",
+            Behaviour::Chaotic => "chaotic",
+            Behaviour::Explosive => "explosive"
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub fn rule_from_number(number: u32) -> Rule {
+    return Rule::Totalistic {
+        become_alive: (0..9).filter(|n| number & (1 << n) != 0).collect(),
+        stay_alive: (0..9).filter(|n| number & (1 << (n + 9)) != 0).collect()
+    }
+}
+
+// Only meaningful for totalistic rules, other rules map to 0.
+pub fn rule_number(rule: &Rule) -> u32 {
+    match rule {
+        Rule::Totalistic { become_alive, stay_alive } => {
+            let birth = become_alive.iter().fold(0, |acc, n| acc | 1 << n);
+            let survival = stay_alive.iter().fold(0, |acc, n| acc | 1 << (n + 9));
+            birth | survival
+        },
+        _ => 0
+    }
+}
+
+fn rule_numbers(rules: &RuleSet) -> Vec<u32> {
+    let has_b0 = |number: &u32| number & 1 != 0;
+    match rules {
+        RuleSet::All => (0..RULE_COUNT).filter(|n| !has_b0(n)).collect(),
+        RuleSet::Random { count, seed } => {
+            let mut random = Random::new(*seed);
+            let mut seen = HashSet::new();
+            let mut numbers = Vec::new();
+            while numbers.len() < (*count).min(RULE_COUNT as usize / 2) {
+                let number = (random.next_u64() % RULE_COUNT as u64) as u32;
+                if !has_b0(&number) && seen.insert(number) {
+                    numbers.push(number);
+                }
+            }
+            numbers
+        },
+        RuleSet::Range { min, max } => {
+            let (min, max) = (rule_number(min), rule_number(max));
+            (0..RULE_COUNT).filter(|n| n & min == min && n & !max == 0 && !has_b0(n)).collect()
+        }
+    }
+}
+
+// Period of the first cluster that comes back in a new position when run on
+// its own. Cells less than three apart are in the same cluster as they can
+// affect each other's next generation. Clusters growing past max_population
+// are given up on.
+fn find_spaceship(grid: &Grid, max_population: usize) -> Option<usize> {
+    let mut cells = grid.get_live_cells();
+    cells.sort_unstable();
+    let mut unvisited: HashSet<(i64, i64)> = cells.iter().copied().collect();
+
+    for start in cells {
+        if !unvisited.remove(&start) {
+            continue;
+        }
+        let mut cluster = vec![start];
+        let mut i = 0;
+        while i < cluster.len() {
+            let (row, col) = cluster[i];
+            for neighbor_row in row - 2..=row + 2 {
+                for neighbor_col in col - 2..=col + 2 {
+                    if unvisited.remove(&(neighbor_row, neighbor_col)) {
+                        cluster.push((neighbor_row, neighbor_col));
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        if let Some(period) = moving_period(grid.get_rule(), &cluster, max_population) {
+            return Some(period)
+        }
+    }
+    return None
+}
+
+fn moving_period(rule: &Rule, cells: &[(i64, i64)], max_population: usize) -> Option<usize> {
+    let mut grid = Grid::new();
+    grid.set_rule(rule.clone());
+    for (row, col) in cells {
+        grid.set_cell(*row, *col, true);
+    }
+    let (start_row, start_col, _, _) = grid.get_bounding_box()?;
+    let shape = shape_hash(&grid, start_row, start_col);
+
+    for period in 1..=MAX_SPACESHIP_PERIOD {
+        grid.calc_next_generation();
+        let (min_row, min_col, _, _) = grid.get_bounding_box()?;
+        if grid.get_population() > max_population {
+            return None
+        }
+        if shape_hash(&grid, min_row, min_col) == shape {
+            return if (min_row, min_col) != (start_row, start_col) { Some(period) } else { None }
+        }
+    }
+    return None
+}
+
+// Seed of the soups of a rule, so rules get different soups and a rule's
+// summary doesn't depend on which other rules are explored (splitmix64).
+fn soup_seed(seed: u64, rule: &Rule) -> u64 {
+    let mut z = seed ^ (rule_number(rule) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    return z ^ (z >> 31)
+}
+
+// Hash of the live cells relative to (min_row, min_col).
+pub fn shape_hash(grid: &Grid, min_row: i64, min_col: i64) -> u64 {
+    let mut cells = grid.get_live_cells();
+    cells.sort_unstable();
+    let mut hasher = DefaultHasher::new();
+    for (row, col) in cells {
+        (row - min_row, col - min_col).hash(&mut hasher);
+    }
+    return hasher.finish()
+}
+
+// xorshift64*, good enough to lay out soups reproducibly.
+pub struct Random {
+    state: u64
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random { state: seed.max(1) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        return self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(rule: &str, cells: &[(i64, i64)]) -> Grid {
+        let mut grid = Grid::new();
+        grid.set_rule(rule.parse().unwrap());
+        for (row, col) in cells {
+            grid.set_cell(*row, *col, true);
+        }
+        return grid
+    }
+
+    fn classify_cells(rule: &str, cells: &[(i64, i64)], config: &ExploreConfig) -> (Behaviour, usize, usize) {
+        let result = classify(grid(rule, cells), config);
+        return (result.behaviour, result.period, result.generation)
+    }
+
+    const GLIDER: [(i64, i64); 5] = [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)];
+
+    #[test]
+    fn classifies_still_lifes_and_oscillators() {
+        let config = ExploreConfig::default();
+        assert_eq!(classify_cells("B3/S23", &[(0, 0), (0, 1), (1, 0), (1, 1)], &config), (Behaviour::Stabilizes, 1, 1));
+        assert_eq!(classify_cells("B3/S23", &[(0, -1), (0, 0), (0, 1)], &config), (Behaviour::Stabilizes, 2, 2));
+        assert_eq!(classify_cells("B3/S23", &[(0, 0), (0, 1)], &config), (Behaviour::Dies, 0, 1));
+    }
+
+    #[test]
+    fn classifies_spaceships() {
+        let config = ExploreConfig::default();
+        assert_eq!(classify_cells("B3/S23", &GLIDER, &config), (Behaviour::Spaceships, 4, 4));
+
+        // The block keeps the pattern from repeating, the glider is found once the box is large enough
+        let mut cells = GLIDER.to_vec();
+        cells.extend([(-10, -10), (-10, -9), (-9, -10), (-9, -9)]);
+        let config = ExploreConfig { spaceship_extent: 20, ..ExploreConfig::default() };
+        assert_eq!(classify_cells("B3/S23", &cells, &config), (Behaviour::Spaceships, 4, 29));
+    }
+
+    #[test]
+    fn growth_without_spaceships_is_not_spaceships() {
+        // B1 grows in every direction without anything moving on its own
+        let config = ExploreConfig { spaceship_extent: 8, max_population: 1024, ..ExploreConfig::default() };
+        let (behaviour, _, _) = classify_cells("B1/S012345678", &[(0, 0)], &config);
+        assert_eq!(behaviour, Behaviour::Explosive);
+
+        // Two blocks far apart never settle into a single shape but never move either
+        let config = ExploreConfig { spaceship_extent: 8, max_generations: 50, ..ExploreConfig::default() };
+        assert_eq!(find_spaceship(&grid("B3/S23", &[(0, 0), (0, 1), (1, 0), (1, 1), (0, 20), (0, 21), (1, 20), (1, 21)]), 4096), None);
+        assert_eq!(classify_cells("B3/S23", &[(0, 0), (0, 1), (1, 0), (1, 1)], &config).0, Behaviour::Stabilizes);
+    }
+
+    #[test]
+    fn classifies_chaotic_patterns() {
+        // The R-pentomino is still active after 100 generations
+        let config = ExploreConfig { max_generations: 100, ..ExploreConfig::default() };
+        assert_eq!(classify_cells("B3/S23", &[(0, 1), (0, 2), (1, 0), (1, 1), (2, 1)], &config), (Behaviour::Chaotic, 0, 100));
+    }
+
+    #[test]
+    fn seeds_soups_per_rule() {
+        let (conway, highlife) = ("B3/S23".parse().unwrap(), "B36/S23".parse().unwrap());
+        assert_eq!(soup_seed(1, &conway), soup_seed(1, &conway));
+        assert_ne!(soup_seed(1, &conway), soup_seed(1, &highlife));
+        assert_ne!(soup_seed(1, &conway), soup_seed(2, &conway));
+
+        let config = ExploreConfig { soups: 4, max_generations: 200, ..ExploreConfig::default() };
+        let summary = summarize_rule(conway.clone(), &config);
+        assert_eq!(summary.counts.values().sum::<usize>(), 4);
+        assert_eq!(summarize_rule(conway, &config).mean_population, summary.mean_population);
+    }
+}
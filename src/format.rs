@@ -0,0 +1,145 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cells::{self, ParseCellsError};
+use crate::life::{self, ParseLifeError, LIFE_105_HEADER, LIFE_106_HEADER};
+use crate::macrocell::{Macrocell, ParseMacrocellError, MACROCELL_HEADER};
+use crate::rle::{ParseRleError, RLE};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Rle,
+    Cells,
+    Life105,
+    Life106,
+    Macrocell
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsePatternError {
+    Rle(ParseRleError),
+    Cells(ParseCellsError),
+    Life(ParseLifeError),
+    Macrocell(ParseMacrocellError)
+}
+
+// Guesses the format from the first lines of a pattern file.
+pub fn detect(s: &str) -> Format {
+    let first_line = s.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    if first_line.starts_with(LIFE_105_HEADER) {
+        return Format::Life105
+    }
+    if first_line.starts_with(LIFE_106_HEADER) {
+        return Format::Life106
+    }
+    if first_line.starts_with(MACROCELL_HEADER) {
+        return Format::Macrocell
+    }
+    if first_line.starts_with('!') {
+        return Format::Cells
+    }
+
+    let first_data = s.lines().find(|l| !l.trim().is_empty() && !l.starts_with('#')).unwrap_or("");
+    if !first_data.is_empty() && first_data.trim_end().chars().all(|c| c == '.' || c == 'O' || c == '*') {
+        return Format::Cells
+    }
+    return Format::Rle
+}
+
+// Life 1.05 and 1.06 share the .lif extension, those have to be detected.
+pub fn from_extension(path: &str) -> Option<Format> {
+    let extension = path.rsplit('.').next()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "rle" => Some(Format::Rle),
+        "cells" => Some(Format::Cells),
+        "mc" => Some(Format::Macrocell),
+        _ => None
+    }
+}
+
+// Names as given on the command line.
+pub fn from_name(name: &str) -> Option<Format> {
+    match name.to_ascii_lowercase().as_str() {
+        "rle" => Some(Format::Rle),
+        "cells" => Some(Format::Cells),
+        "life105" | "lif105" => Some(Format::Life105),
+        "life106" | "lif106" => Some(Format::Life106),
+        "mc" | "macrocell" => Some(Format::Macrocell),
+        _ => None
+    }
+}
+
+pub fn parse(s: &str) -> Result<RLE, ParsePatternError> {
+    return parse_as(s, detect(s))
+}
+
+pub fn parse_as(s: &str, format: Format) -> Result<RLE, ParsePatternError> {
+    match format {
+        Format::Rle => Ok(RLE::from_str(s)?),
+        Format::Cells => Ok(cells::parse(s)?),
+        Format::Life105 => Ok(life::parse_105(s)?),
+        Format::Life106 => Ok(life::parse_106(s)?),
+        Format::Macrocell => {
+            let macrocell = Macrocell::from_str(s)?;
+            let rule = macrocell.rule().cloned().unwrap_or_default();
+            let mut rle = RLE::from_states(&macrocell.get_live_cell_states(), &rule);
+            for comment in macrocell.comments() {
+                rle.add_comment(comment);
+            }
+            Ok(rle)
+        }
+    }
+}
+
+pub fn write(rle: &RLE, format: Format) -> String {
+    match format {
+        Format::Rle => rle.to_string(),
+        Format::Cells => cells::write(rle),
+        Format::Life105 => life::write_105(rle),
+        Format::Life106 => life::write_106(rle),
+        Format::Macrocell => {
+            let mut macrocell = Macrocell::from_states(&rle.get_live_cell_states(), &rle.rule().cloned().unwrap_or_default());
+            for comment in rle.comments() {
+                macrocell.add_comment(comment);
+            }
+            macrocell.to_string()
+        }
+    }
+}
+
+impl From<ParseRleError> for ParsePatternError {
+    fn from(error: ParseRleError) -> ParsePatternError {
+        ParsePatternError::Rle(error)
+    }
+}
+
+impl From<ParseCellsError> for ParsePatternError {
+    fn from(error: ParseCellsError) -> ParsePatternError {
+        ParsePatternError::Cells(error)
+    }
+}
+
+impl From<ParseLifeError> for ParsePatternError {
+    fn from(error: ParseLifeError) -> ParsePatternError {
+        ParsePatternError::Life(error)
+    }
+}
+
+impl From<ParseMacrocellError> for ParsePatternError {
+    fn from(error: ParseMacrocellError) -> ParsePatternError {
+        ParsePatternError::Macrocell(error)
+    }
+}
+
+impl fmt::Display for ParsePatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParsePatternError::Rle(e) => write!(f, "RLE: {}", e),
+            ParsePatternError::Cells(e) => write!(f, "plaintext: {}", e),
+            ParsePatternError::Life(e) => write!(f, "Life: {}", e),
+            ParsePatternError::Macrocell(e) => write!(f, "macrocell: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for ParsePatternError {}
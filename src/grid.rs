@@ -1,17 +1,70 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use nalgebra_glm::Vec2;
 
-pub struct Rule {
-    pub become_alive: Vec<usize>,
-    pub stay_alive: Vec<usize>
+// Neighborhoods are numbered like the transition table of Golly's MAP rules:
+// bit 8 is the north-west cell, bit 4 the cell itself and bit 0 the south-east cell.
+pub const NEIGHBORHOOD_COUNT: usize = 512;
+const CENTER_BIT: usize = 1 << 4;
+const OUTER_BITS: usize = (NEIGHBORHOOD_COUNT - 1) & !CENTER_BIT;
+
+pub const HENSEL_LETTERS: [&str; 9] = ["", "ce", "ceaikn", "ceaiknjqry", "ceaiknjqrtwyz", "ceaiknjqry", "ceaikn", "ce", ""];
+
+// One neighborhood per Hensel letter for up to 4 neighbors. Counts above 4 use
+// the complement of the neighborhood with the same letter for 8 - count.
+const HENSEL_NEIGHBORHOODS: [&[usize]; 5] = [
+    &[0],
+    &[1, 2],
+    &[5, 10, 3, 40, 33, 68],
+    &[69, 42, 11, 7, 98, 13, 14, 70, 41, 97],
+    &[325, 170, 15, 45, 99, 71, 106, 102, 43, 101, 105, 78, 108]
+];
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const MAP_LENGTH: usize = NEIGHBORHOOD_COUNT.div_ceil(6);
+
+// Rectangle of cells (min_row, min_col, max_row, max_col), both corners included
+pub type Region = (i64, i64, i64, i64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    Totalistic {
+        become_alive: Vec<usize>,
+        stay_alive: Vec<usize>
+    },
+    // One bitmask per neighbor count, bit i is set if the i-th letter of
+    // HENSEL_LETTERS[count] is part of the rule.
+    Isotropic {
+        become_alive: [u16; 9],
+        stay_alive: [u16; 9]
+    },
+    Map(Box<[bool; NEIGHBORHOOD_COUNT]>)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRuleError {
+    Empty,
+    InvalidNeighborCount(char),
+    InvalidHenselLetter(char, usize),
+    UnexpectedCharacter(char, usize),
+    DuplicateSection(char),
+    WrongPartCount(usize),
+    InvalidMapLength(usize),
+    InvalidBase64(char, usize)
 }
 
+// Cells hold a state from 0 (dead) to 255 so multi-state patterns can be
+// loaded. The rules are two-state: any non-zero state counts as alive and
+// cells alive in the next generation are in state 1.
+#[derive(Clone)]
 pub struct Grid {
-    first_hash_map: HashMap<(i64, i64), bool>,
-    second_hash_map: HashMap<(i64, i64), bool>,
+    first_hash_map: HashMap<(i64, i64), u8>,
+    second_hash_map: HashMap<(i64, i64), u8>,
     generation: bool,
-    rule: Rule
+    rule: Rule,
+    rule_table: Box<[bool; NEIGHBORHOOD_COUNT]>
 }
 
 impl Grid {
@@ -20,17 +73,20 @@ impl Grid {
             first_hash_map: HashMap::new(),
             second_hash_map: HashMap::new(),
             generation: false,
-            rule: Rule {
-                become_alive: vec![3],
-                stay_alive: vec![2, 3]
-            }
+            rule: Rule::default(),
+            rule_table: Rule::default().table()
         }
     }
 
     pub fn set_rule(&mut self, rule: Rule) {
+        self.rule_table = rule.table();
         self.rule = rule;
     }
 
+    pub fn get_rule(&self) -> &Rule {
+        return &self.rule
+    }
+
     pub fn get_active_cells(&self, result: &mut Vec<Vec2>) {
         let current_hash_map = match self.generation {
             true => &self.first_hash_map,
@@ -47,6 +103,32 @@ impl Grid {
         }
     }
 
+    pub fn get_live_cells(&self) -> Vec<(i64, i64)> {
+        return self.current_hash_map().keys().cloned().collect()
+    }
+
+    pub fn get_live_cell_states(&self) -> Vec<(i64, i64, u8)> {
+        return self.current_hash_map().iter().map(|((row, col), state)| (*row, *col, *state)).collect()
+    }
+
+    pub fn get_population(&self) -> usize {
+        return self.current_hash_map().len()
+    }
+
+    // Returns the smallest region containing all live cells.
+    pub fn get_bounding_box(&self) -> Option<Region> {
+        let mut cells = self.current_hash_map().keys();
+        let (row, col) = cells.next()?;
+        let mut bounds = (*row, *col, *row, *col);
+        for (row, col) in cells {
+            bounds.0 = bounds.0.min(*row);
+            bounds.1 = bounds.1.min(*col);
+            bounds.2 = bounds.2.max(*row);
+            bounds.3 = bounds.3.max(*col);
+        }
+        return Some(bounds)
+    }
+
     pub fn get_grid(&self, row: i64, col: i64, len: usize, result: &mut Vec<f32>) {
         /*if self.generation {
             for ((row, col), val) in &self.first_hash_map {
@@ -68,7 +150,7 @@ impl Grid {
     pub fn calc_next_generation(&mut self) {
         if self.generation {
             for ((row, col), val) in &self.first_hash_map {
-                if *val {
+                if *val != 0 {
                     for neighbor_row in -1..2 {
                         for neighbor_col in -1..2 {
                             if neighbor_col == 0 && neighbor_row == 0 {
@@ -79,20 +161,20 @@ impl Grid {
                             let neighbor_col_index = col + neighbor_col;
                             
                             if !self.get_cell(neighbor_row_index,  neighbor_col_index) {
-                                let neighbor_next_value = self.get_cell_next_generation(neighbor_row_index,  neighbor_col_index, false);
-                                set_cell_in_hashmap(neighbor_row_index,  neighbor_col_index, neighbor_next_value, &mut self.second_hash_map);
+                                let neighbor_next_value = self.get_cell_next_generation(neighbor_row_index,  neighbor_col_index);
+                                set_state_in_hashmap(neighbor_row_index,  neighbor_col_index, neighbor_next_value as u8, &mut self.second_hash_map);
                             }
                         }
                     }
                 }
                     
-                let next_value = self.get_cell_next_generation(*row, *col, *val);
-                set_cell_in_hashmap(*row, *col, next_value, &mut self.second_hash_map);
+                let next_value = self.get_cell_next_generation(*row, *col);
+                set_state_in_hashmap(*row, *col, next_value as u8, &mut self.second_hash_map);
             }
             self.first_hash_map.clear();
         } else {
             for ((row, col), val) in &self.second_hash_map {
-                if *val {
+                if *val != 0 {
                     for neighbor_row in -1..2 {
                         for neighbor_col in -1..2 {
                             if neighbor_col == 0 && neighbor_row == 0 {
@@ -103,15 +185,15 @@ impl Grid {
                             let neighbor_col_index = col + neighbor_col;
                             
                             if !self.get_cell(neighbor_row_index,  neighbor_col_index) {
-                                let neighbor_next_value = self.get_cell_next_generation(neighbor_row_index,  neighbor_col_index, false);
-                                set_cell_in_hashmap(neighbor_row_index,  neighbor_col_index, neighbor_next_value, &mut self.first_hash_map);
+                                let neighbor_next_value = self.get_cell_next_generation(neighbor_row_index,  neighbor_col_index);
+                                set_state_in_hashmap(neighbor_row_index,  neighbor_col_index, neighbor_next_value as u8, &mut self.first_hash_map);
                             }
                         }
                     }
                 }
                 
-                let next_value = self.get_cell_next_generation(*row, *col, *val);
-                set_cell_in_hashmap(*row, *col, next_value, &mut self.first_hash_map);
+                let next_value = self.get_cell_next_generation(*row, *col);
+                set_state_in_hashmap(*row, *col, next_value as u8, &mut self.first_hash_map);
             }
             self.second_hash_map.clear();
         }
@@ -121,56 +203,606 @@ impl Grid {
     }
 
     pub fn get_cell(&self, row: i64, col: i64) -> bool {
+        return self.get_state(row, col) != 0
+    }
+
+    pub fn set_cell(&mut self, row: i64, col: i64, value: bool) {
+        self.set_state(row, col, value as u8);
+    }
+
+    pub fn get_state(&self, row: i64, col: i64) -> u8 {
         if self.generation {
-            return get_cell_from_hashmap(row, col, &self.first_hash_map)
+            return get_state_from_hashmap(row, col, &self.first_hash_map)
         } else {
-            return get_cell_from_hashmap(row, col, &self.second_hash_map)
+            return get_state_from_hashmap(row, col, &self.second_hash_map)
         }
     }
 
-    pub fn set_cell(&mut self, row: i64, col: i64, value: bool) {
+    pub fn set_state(&mut self, row: i64, col: i64, state: u8) {
         if self.generation {
-            set_cell_in_hashmap(row, col, value, &mut self.first_hash_map);
+            set_state_in_hashmap(row, col, state, &mut self.first_hash_map);
         } else {
-            set_cell_in_hashmap(row, col, value, &mut self.second_hash_map);
+            set_state_in_hashmap(row, col, state, &mut self.second_hash_map);
         }
     }
 
-    fn get_cell_next_generation(&self, row: i64, col: i64, val: bool) -> bool {
-        let mut neighbor_counter: usize = 0;
+    pub fn get_neighborhood(&self, row: i64, col: i64) -> usize {
+        let mut neighborhood: usize = 0;
     
         for neighbor_row in -1..2 {
             for neighbor_col in -1..2 {
-                if neighbor_col == 0 && neighbor_row == 0 {
-                    continue;
-                }
-                
+                neighborhood <<= 1;
                 if self.get_cell(row + neighbor_row,  col + neighbor_col) {
-                    neighbor_counter += 1;
+                    neighborhood |= 1;
                 }
             }
         }
 
-        if !val {
-            return self.rule.become_alive.contains(&neighbor_counter);
-        }
-        else {
-            return self.rule.stay_alive.contains(&neighbor_counter);
+        return neighborhood
+    }
+
+    fn current_hash_map(&self) -> &HashMap<(i64, i64), u8> {
+        match self.generation {
+            true => &self.first_hash_map,
+            false => &self.second_hash_map
         }
     }
+
+    fn get_cell_next_generation(&self, row: i64, col: i64) -> bool {
+        return self.rule_table[self.get_neighborhood(row, col)];
+    }
 }
 
-fn get_cell_from_hashmap(row: i64, col: i64, map: &HashMap<(i64, i64), bool>) -> bool {
+fn get_state_from_hashmap(row: i64, col: i64, map: &HashMap<(i64, i64), u8>) -> u8 {
     match map.get(&(row, col)) {
-        Some(b) => *b,
-        None => false
+        Some(state) => *state,
+        None => 0
     }
 }
 
-fn set_cell_in_hashmap(row: i64, col: i64, value: bool, map: &mut HashMap<(i64, i64), bool>) {
-    if value {
-        map.insert((row, col), value);
+fn set_state_in_hashmap(row: i64, col: i64, state: u8, map: &mut HashMap<(i64, i64), u8>) {
+    if state != 0 {
+        map.insert((row, col), state);
     } else {
         map.remove(&(row, col));
     }
-}
\ No newline at end of file
+}
+
+impl Rule {
+    pub fn next_state(&self, neighborhood: usize) -> bool {
+        let alive = neighborhood & CENTER_BIT != 0;
+
+        match self {
+            Rule::Totalistic { become_alive, stay_alive } => {
+                let count = (neighborhood & OUTER_BITS).count_ones() as usize;
+                if alive { stay_alive.contains(&count) } else { become_alive.contains(&count) }
+            },
+            Rule::Isotropic { become_alive, stay_alive } => {
+                let (count, letter) = hensel_class(neighborhood);
+                let masks = if alive { stay_alive } else { become_alive };
+                masks[count] & (1 << letter) != 0
+            },
+            Rule::Map(table) => table[neighborhood]
+        }
+    }
+
+    pub fn table(&self) -> Box<[bool; NEIGHBORHOOD_COUNT]> {
+        let mut table = Box::new([false; NEIGHBORHOOD_COUNT]);
+        for (neighborhood, next) in table.iter_mut().enumerate() {
+            *next = self.next_state(neighborhood);
+        }
+        return table
+    }
+
+    pub fn to_map(&self) -> Rule {
+        return Rule::Map(self.table())
+    }
+
+    // Falls back to a totalistic rule when every neighbor count is either fully
+    // included or excluded.
+    pub fn from_hensel_masks(become_alive: [u16; 9], stay_alive: [u16; 9]) -> Rule {
+        let is_totalistic = |masks: &[u16; 9]| {
+            masks.iter().enumerate().all(|(count, mask)| *mask == 0 || *mask == full_hensel_mask(count))
+        };
+        let counts = |masks: &[u16; 9]| -> Vec<usize> {
+            (0..9).filter(|count| masks[*count] != 0).collect()
+        };
+
+        if is_totalistic(&become_alive) && is_totalistic(&stay_alive) {
+            return Rule::Totalistic {
+                become_alive: counts(&become_alive),
+                stay_alive: counts(&stay_alive)
+            }
+        }
+
+        return Rule::Isotropic {
+            become_alive,
+            stay_alive
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Rule {
+        return Rule::Totalistic {
+            become_alive: vec![3],
+            stay_alive: vec![2, 3]
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    // Accepts "B3/S23" (and "B3S23", any case, either section first) with
+    // optional Hensel letters like "B2a/S12-k", the older "23/3" notation,
+    // which lists survival before birth, and Golly's "MAP..." rules.
+    // Golly's bounded grid suffix like ":T30,20" is dropped, the grid here
+    // is always unbounded. The only rule name known is Golly's "Life".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = match s.find(':') {
+            Some(pos) => match s[pos + 1..].chars().next() {
+                Some('P') | Some('T') | Some('K') | Some('C') | Some('S') => s[..pos].trim_end(),
+                Some(c) => return Err(ParseRuleError::UnexpectedCharacter(c, pos + 1)),
+                None => return Err(ParseRuleError::UnexpectedCharacter(':', pos))
+            },
+            None => s
+        };
+        if s.is_empty() {
+            return Err(ParseRuleError::Empty)
+        }
+
+        if s == "Life" {
+            Ok(Rule::default())
+        } else if let Some(map) = s.strip_prefix("MAP") {
+            parse_map_rule(map)
+        } else if s.chars().any(|c| c.is_ascii_alphabetic()) {
+            parse_bs_rule(s)
+        } else {
+            parse_sb_rule(s)
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::Totalistic { become_alive, stay_alive } => {
+                write!(f, "B")?;
+                for n in become_alive {
+                    write!(f, "{}", n)?;
+                }
+                write!(f, "/S")?;
+                for n in stay_alive {
+                    write!(f, "{}", n)?;
+                }
+            },
+            Rule::Isotropic { become_alive, stay_alive } => {
+                write!(f, "B")?;
+                write_hensel_masks(f, become_alive)?;
+                write!(f, "/S")?;
+                write_hensel_masks(f, stay_alive)?;
+            },
+            Rule::Map(table) => {
+                write!(f, "MAP")?;
+                for group in 0..MAP_LENGTH {
+                    let mut value = 0;
+                    for bit in (group * 6)..(group * 6 + 6) {
+                        value <<= 1;
+                        if bit < NEIGHBORHOOD_COUNT && table[bit] {
+                            value |= 1;
+                        }
+                    }
+                    write!(f, "{}", BASE64_ALPHABET[value] as char)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRuleError::Empty => write!(f, "rule string is empty"),
+            ParseRuleError::InvalidNeighborCount(c) => write!(f, "invalid neighbor count '{}', expected 0-8", c),
+            ParseRuleError::InvalidHenselLetter(c, count) => write!(f, "'{}' is not a valid Hensel letter for {} neighbors", c, count),
+            ParseRuleError::UnexpectedCharacter(c, pos) => write!(f, "unexpected character '{}' at position {}", c, pos),
+            ParseRuleError::DuplicateSection(c) => write!(f, "section '{}' appears more than once", c),
+            ParseRuleError::WrongPartCount(n) => write!(f, "expected 2 parts separated by '/', found {}", n),
+            ParseRuleError::InvalidMapLength(n) => write!(f, "MAP rule needs {} base64 characters, found {}", MAP_LENGTH, n),
+            ParseRuleError::InvalidBase64(c, pos) => write!(f, "invalid base64 character '{}' at position {} of MAP rule", c, pos)
+        }
+    }
+}
+
+impl std::error::Error for ParseRuleError {}
+
+// Returns the neighbor count and the index of its Hensel letter.
+pub fn hensel_class(neighborhood: usize) -> (usize, usize) {
+    let outer = neighborhood & OUTER_BITS;
+    let count = outer.count_ones() as usize;
+    if count == 0 || count == 8 {
+        return (count, 0)
+    }
+
+    let symmetries = neighborhood_symmetries(outer);
+    let (representatives, complement) = if count <= 4 {
+        (HENSEL_NEIGHBORHOODS[count], 0)
+    } else {
+        (HENSEL_NEIGHBORHOODS[8 - count], OUTER_BITS)
+    };
+
+    for (letter, representative) in representatives.iter().enumerate() {
+        if symmetries.contains(&(representative ^ complement)) {
+            return (count, letter)
+        }
+    }
+    unreachable!("no Hensel class for neighborhood {}", neighborhood)
+}
+
+pub fn hensel_class_count(count: usize) -> usize {
+    return HENSEL_LETTERS[count].len().max(1)
+}
+
+fn neighborhood_symmetries(neighborhood: usize) -> [usize; 8] {
+    let mut result = [0; 8];
+    let mut current = neighborhood;
+    for i in 0..4 {
+        result[2 * i] = current;
+        result[2 * i + 1] = reflect_neighborhood(current);
+        current = rotate_neighborhood(current);
+    }
+    return result
+}
+
+fn cell_bit(row: usize, col: usize) -> usize {
+    return 1 << (8 - (row * 3 + col))
+}
+
+// Rotates a neighborhood by 90 degrees clockwise.
+fn rotate_neighborhood(neighborhood: usize) -> usize {
+    let mut result = 0;
+    for row in 0..3 {
+        for col in 0..3 {
+            if neighborhood & cell_bit(2 - col, row) != 0 {
+                result |= cell_bit(row, col);
+            }
+        }
+    }
+    return result
+}
+
+// Mirrors a neighborhood along its vertical axis.
+fn reflect_neighborhood(neighborhood: usize) -> usize {
+    let mut result = 0;
+    for row in 0..3 {
+        for col in 0..3 {
+            if neighborhood & cell_bit(row, 2 - col) != 0 {
+                result |= cell_bit(row, col);
+            }
+        }
+    }
+    return result
+}
+
+pub fn full_hensel_mask(count: usize) -> u16 {
+    return ((1u32 << hensel_class_count(count)) - 1) as u16
+}
+
+fn write_hensel_masks(f: &mut fmt::Formatter<'_>, masks: &[u16; 9]) -> fmt::Result {
+    for (count, mask) in masks.iter().enumerate() {
+        if *mask == 0 {
+            continue;
+        }
+        write!(f, "{}", count)?;
+        if *mask == full_hensel_mask(count) {
+            continue;
+        }
+
+        let letters = HENSEL_LETTERS[count];
+        let included = mask.count_ones() as usize;
+        let (negate, selected) = if included <= letters.len() - included {
+            (false, *mask)
+        } else {
+            (true, !*mask & full_hensel_mask(count))
+        };
+
+        if negate {
+            write!(f, "-")?;
+        }
+        for (i, letter) in letters.chars().enumerate() {
+            if selected & (1 << i) != 0 {
+                write!(f, "{}", letter)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_bs_rule(s: &str) -> Result<Rule, ParseRuleError> {
+    let mut become_alive: Option<[u16; 9]> = None;
+    let mut stay_alive: Option<[u16; 9]> = None;
+    let mut current: Option<char> = None;
+    // Neighbor count being parsed, the position of its '-' if it is negated
+    // and its letters so far
+    let mut pending: Option<(usize, Option<usize>, u16)> = None;
+
+    for (pos, c) in s.char_indices() {
+        match c {
+            'B' | 'b' | 'S' | 's' => {
+                flush_hensel_count(&mut pending, current, &mut become_alive, &mut stay_alive)?;
+                let section = c.to_ascii_uppercase();
+                let target = if section == 'B' { &mut become_alive } else { &mut stay_alive };
+                if target.is_some() {
+                    return Err(ParseRuleError::DuplicateSection(section))
+                }
+                *target = Some([0; 9]);
+                current = Some(section);
+            },
+            '/' if current.is_some() => {
+                flush_hensel_count(&mut pending, current, &mut become_alive, &mut stay_alive)?;
+                current = None;
+            },
+            c if c.is_whitespace() => {},
+            '0'..='9' if current.is_some() => {
+                flush_hensel_count(&mut pending, current, &mut become_alive, &mut stay_alive)?;
+                pending = Some((parse_neighbor_count(c)?, None, 0));
+            },
+            '-' => match pending.as_mut() {
+                Some((_, negated, 0)) if negated.is_none() => *negated = Some(pos),
+                _ => return Err(ParseRuleError::UnexpectedCharacter(c, pos))
+            },
+            c if c.is_ascii_lowercase() && pending.is_some() => {
+                let (count, _, letters) = pending.as_mut().unwrap();
+                match HENSEL_LETTERS[*count].find(c) {
+                    Some(index) => *letters |= 1 << index,
+                    None => return Err(ParseRuleError::InvalidHenselLetter(c, *count))
+                }
+            },
+            _ => return Err(ParseRuleError::UnexpectedCharacter(c, pos))
+        }
+    }
+    flush_hensel_count(&mut pending, current, &mut become_alive, &mut stay_alive)?;
+
+    return Ok(Rule::from_hensel_masks(become_alive.unwrap_or_default(), stay_alive.unwrap_or_default()))
+}
+
+// A '-' has to be followed by the letters it removes.
+fn flush_hensel_count(pending: &mut Option<(usize, Option<usize>, u16)>, section: Option<char>, become_alive: &mut Option<[u16; 9]>, stay_alive: &mut Option<[u16; 9]>) -> Result<(), ParseRuleError> {
+    if let Some((count, negated, letters)) = pending.take() {
+        let full = full_hensel_mask(count);
+        let mask = match (negated, letters) {
+            (Some(pos), 0) => return Err(ParseRuleError::UnexpectedCharacter('-', pos)),
+            (None, 0) => full,
+            (None, letters) => letters,
+            (Some(_), letters) => full & !letters
+        };
+        let masks = match section {
+            Some('B') => become_alive.as_mut().unwrap(),
+            _ => stay_alive.as_mut().unwrap()
+        };
+        masks[count] |= mask;
+    }
+    Ok(())
+}
+
+fn parse_sb_rule(s: &str) -> Result<Rule, ParseRuleError> {
+    let parts: Vec<&str> = s.split('/').collect();
+    if parts.len() != 2 {
+        return Err(ParseRuleError::WrongPartCount(parts.len()))
+    }
+
+    let mut counts: Vec<Vec<usize>> = Vec::new();
+    let mut offset = 0;
+    for part in parts {
+        let mut values = Vec::new();
+        for (pos, c) in part.char_indices() {
+            if c.is_whitespace() {
+                continue;
+            }
+            if !c.is_ascii_digit() {
+                return Err(ParseRuleError::UnexpectedCharacter(c, offset + pos))
+            }
+            values.push(parse_neighbor_count(c)?);
+        }
+        counts.push(normalize_counts(values));
+        offset += part.len() + 1;
+    }
+
+    let become_alive = counts.pop().unwrap();
+    let stay_alive = counts.pop().unwrap();
+    return Ok(Rule::Totalistic {
+        become_alive,
+        stay_alive
+    })
+}
+
+fn parse_map_rule(s: &str) -> Result<Rule, ParseRuleError> {
+    let data = s.trim_end_matches('=');
+    let length = data.chars().count();
+    if length != MAP_LENGTH {
+        return Err(ParseRuleError::InvalidMapLength(length))
+    }
+
+    let mut table = Box::new([false; NEIGHBORHOOD_COUNT]);
+    for (group, c) in data.chars().enumerate() {
+        let value = match BASE64_ALPHABET.iter().position(|b| *b as char == c) {
+            Some(v) => v,
+            None => return Err(ParseRuleError::InvalidBase64(c, group + 3))
+        };
+
+        for i in 0..6 {
+            let bit = group * 6 + i;
+            if bit < NEIGHBORHOOD_COUNT {
+                table[bit] = value & (1 << (5 - i)) != 0;
+            }
+        }
+    }
+
+    return Ok(Rule::Map(table))
+}
+
+fn parse_neighbor_count(c: char) -> Result<usize, ParseRuleError> {
+    match c.to_digit(10) {
+        Some(num) if num <= 8 => Ok(num as usize),
+        _ => Err(ParseRuleError::InvalidNeighborCount(c))
+    }
+}
+
+fn normalize_counts(mut counts: Vec<usize>) -> Vec<usize> {
+    counts.sort_unstable();
+    counts.dedup();
+    return counts
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totalistic(become_alive: &[usize], stay_alive: &[usize]) -> Rule {
+        return Rule::Totalistic { become_alive: become_alive.to_vec(), stay_alive: stay_alive.to_vec() }
+    }
+
+    #[test]
+    fn parses_and_formats_rule_strings() {
+        let cases = [
+            ("B3/S23", totalistic(&[3], &[2, 3]), "B3/S23"),
+            ("b3/s23", totalistic(&[3], &[2, 3]), "B3/S23"),
+            ("B3S23", totalistic(&[3], &[2, 3]), "B3/S23"),
+            ("S23/B3", totalistic(&[3], &[2, 3]), "B3/S23"),
+            ("23/3", totalistic(&[3], &[2, 3]), "B3/S23"),
+            ("B/S", totalistic(&[], &[]), "B/S"),
+            ("/", totalistic(&[], &[]), "B/S"),
+            ("B2/S", totalistic(&[2], &[]), "B2/S"),
+            ("B33/S32", totalistic(&[3], &[2, 3]), "B3/S23"),
+            ("323/3", totalistic(&[3], &[2, 3]), "B3/S23"),
+            ("B36/S23:T100,100", totalistic(&[3, 6], &[2, 3]), "B36/S23")
+        ];
+        for (text, rule, formatted) in cases.iter() {
+            let parsed: Rule = text.parse().unwrap();
+            assert_eq!(parsed, *rule, "{}", text);
+            assert_eq!(parsed.to_string(), *formatted, "{}", text);
+            assert_eq!(formatted.parse::<Rule>().unwrap(), parsed, "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_rule_strings() {
+        let cases = [
+            ("", ParseRuleError::Empty),
+            ("B9/S23", ParseRuleError::InvalidNeighborCount('9')),
+            ("239/3", ParseRuleError::InvalidNeighborCount('9')),
+            ("B3/B3", ParseRuleError::DuplicateSection('B')),
+            ("23/3/3", ParseRuleError::WrongPartCount(3)),
+            ("B3x/S23", ParseRuleError::InvalidHenselLetter('x', 3)),
+            ("B3-/S23", ParseRuleError::UnexpectedCharacter('-', 2)),
+            ("B3/S23:Q", ParseRuleError::UnexpectedCharacter('Q', 7)),
+            ("MAPABC", ParseRuleError::InvalidMapLength(3))
+        ];
+        for (text, error) in cases.iter() {
+            assert_eq!(text.parse::<Rule>().err().as_ref(), Some(error), "{}", text);
+        }
+    }
+
+    #[test]
+    fn only_life_is_a_known_rule_name() {
+        assert_eq!("Life".parse::<Rule>(), Ok(Rule::default()));
+        for name in ["life", "HighLife", "LifeHistory", "WireWorld", "B3/S23/3", "B2/S/C3"] {
+            assert!(name.parse::<Rule>().is_err(), "{}", name);
+        }
+    }
+
+    const CONWAY_MAP: &str = "MAPARYXfhZofugWaH7oaIDogBZofuhogOiAaIDogIAAgAAWaH7oaIDogGiA6ICAAIAAaIDogIAAgACAAIAAAAAAAA";
+
+    fn sorted_cells(grid: &Grid) -> Vec<(i64, i64)> {
+        let mut cells = grid.get_live_cells();
+        cells.sort_unstable();
+        return cells
+    }
+
+    fn grid_with(rule: Rule, cells: &[(i64, i64)]) -> Grid {
+        let mut grid = Grid::new();
+        grid.set_rule(rule);
+        for (row, col) in cells {
+            grid.set_cell(*row, *col, true);
+        }
+        return grid
+    }
+
+    #[test]
+    fn conway_map_rule_steps_like_b3_s23() {
+        let map: Rule = CONWAY_MAP.parse().unwrap();
+        assert_eq!(map.table(), Rule::default().table());
+
+        let glider = [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)];
+        let mut map_grid = grid_with(map, &glider);
+        let mut life_grid = grid_with(Rule::default(), &glider);
+        for _ in 0..8 {
+            map_grid.calc_next_generation();
+            life_grid.calc_next_generation();
+            assert_eq!(sorted_cells(&map_grid), sorted_cells(&life_grid));
+        }
+        // Two periods move the glider by (2, 2)
+        let moved: Vec<(i64, i64)> = glider.iter().map(|(row, col)| (row + 2, col + 2)).collect();
+        assert_eq!(sorted_cells(&map_grid), moved);
+    }
+
+    #[test]
+    fn map_rules_round_trip() {
+        let map: Rule = CONWAY_MAP.parse().unwrap();
+        assert_eq!(map.to_string(), CONWAY_MAP);
+        assert_eq!(Rule::default().to_map().to_string(), CONWAY_MAP);
+
+        // Golly also writes the base64 padding
+        let padded: Rule = format!("{}==", CONWAY_MAP).parse().unwrap();
+        assert_eq!(padded, map);
+
+        let highlife = "B36/S23".parse::<Rule>().unwrap().to_map();
+        assert_eq!(highlife.to_string().parse::<Rule>().unwrap(), highlife);
+    }
+
+    #[test]
+    fn isotropic_rule_skips_excluded_hensel_letters() {
+        let rule: Rule = "B2-a/S12".parse().unwrap();
+        assert_eq!(rule.to_string(), "B2-a/S12");
+
+        // Both dead cells next to a diagonal pair see two edge neighbors (2e)
+        // and are born, the block then dies as its cells have 3 neighbors and
+        // the cells around it only see an edge and a corner next to it (2a).
+        let mut grid = grid_with(rule.clone(), &[(0, 0), (1, 1)]);
+        grid.calc_next_generation();
+        assert_eq!(sorted_cells(&grid), vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        grid.calc_next_generation();
+        assert!(sorted_cells(&grid).is_empty());
+
+        // A domino is still as every birth next to it would be 2a
+        let mut grid = grid_with(rule, &[(0, 0), (0, 1)]);
+        grid.calc_next_generation();
+        assert_eq!(sorted_cells(&grid), vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn hensel_classes_per_neighbor_count() {
+        let mut letters: Vec<Vec<usize>> = vec![Vec::new(); 9];
+        for neighborhood in 0..NEIGHBORHOOD_COUNT {
+            let (count, letter) = hensel_class(neighborhood);
+            assert_eq!(count, (neighborhood & OUTER_BITS).count_ones() as usize);
+            if !letters[count].contains(&letter) {
+                letters[count].push(letter);
+            }
+        }
+        let class_counts: Vec<usize> = letters.iter().map(|l| l.len()).collect();
+        assert_eq!(class_counts, vec![1, 2, 6, 10, 13, 10, 6, 2, 1]);
+        for (count, classes) in class_counts.iter().enumerate() {
+            assert_eq!(hensel_class_count(count), *classes);
+            assert_eq!(full_hensel_mask(count).count_ones() as usize, *classes);
+        }
+    }
+
+    #[test]
+    fn full_hensel_masks_fall_back_to_totalistic() {
+        let rule: Rule = "B2cekinaS12".parse().unwrap();
+        assert_eq!(rule, totalistic(&[2], &[1, 2]));
+    }
+}
@@ -0,0 +1,247 @@
+// A small zlib encoder for the PNG writer: greedy LZ77 matching with a hash
+// of the next three bytes, written as one block with the fixed Huffman codes.
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_SIZE: usize = 1 << 15;
+
+const LENGTH_BASES: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA_BITS: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577
+];
+const DISTANCE_EXTRA_BITS: [u32; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bit_count: u32
+}
+
+impl BitWriter {
+    // Deflate packs values starting at the least significant bit.
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= value << self.bit_count;
+        self.bit_count += bits;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    // Huffman codes are stored starting at their most significant bit.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        let reversed = code.reverse_bits() >> (32 - bits);
+        self.write(reversed, bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        return self.bytes
+    }
+}
+
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window, no preset dictionary, fastest compression level
+    let mut result = vec![0x78, 0x01];
+    result.extend(deflate(data));
+    result.extend(&adler32(data).to_be_bytes());
+    return result
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { bytes: Vec::new(), buffer: 0, bit_count: 0 };
+    // Final block with fixed Huffman codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            let hash = hash(&data[pos..pos + MIN_MATCH]);
+            let candidate = head[hash];
+            head[hash] = pos;
+            if candidate != usize::MAX && pos - candidate <= WINDOW_SIZE {
+                let max_length = MAX_MATCH.min(data.len() - pos);
+                let length = (0..max_length).take_while(|i| data[candidate + i] == data[pos + i]).count();
+                if length >= MIN_MATCH {
+                    best = (length, pos - candidate);
+                }
+            }
+        }
+
+        let (length, distance) = best;
+        if length == 0 {
+            write_literal(&mut writer, data[pos] as usize);
+            pos += 1;
+            continue;
+        }
+
+        write_match(&mut writer, length, distance);
+        for skipped in pos + 1..(pos + length).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+            head[hash(&data[skipped..skipped + MIN_MATCH])] = skipped;
+        }
+        pos += length;
+    }
+
+    write_literal(&mut writer, 256);
+    return writer.finish()
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize;
+    return (value.wrapping_mul(2654435761) >> 8) & (HASH_SIZE - 1)
+}
+
+// Fixed literal/length codes: 0-143 use 8 bits, 144-255 9 bits, 256-279 7 bits
+// and 280-287 8 bits.
+fn write_literal(writer: &mut BitWriter, symbol: usize) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8)
+    }
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASES.iter().rposition(|base| *base <= length).unwrap();
+    write_literal(writer, 257 + code);
+    writer.write((length - LENGTH_BASES[code]) as u32, LENGTH_EXTRA_BITS[code]);
+
+    let code = DISTANCE_BASES.iter().rposition(|base| *base <= distance).unwrap();
+    writer.write_code(code as u32, 5);
+    writer.write((distance - DISTANCE_BASES[code]) as u32, DISTANCE_EXTRA_BITS[code]);
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    return b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct BitReader<'a> {
+        bytes: &'a [u8],
+        pos: usize
+    }
+
+    impl<'a> BitReader<'a> {
+        fn bit(&mut self) -> u32 {
+            let bit = (self.bytes[self.pos / 8] >> (self.pos % 8)) & 1;
+            self.pos += 1;
+            return bit as u32
+        }
+
+        fn bits(&mut self, count: u32) -> u32 {
+            return (0..count).fold(0, |value, i| value | self.bit() << i)
+        }
+
+        // Huffman codes start at their most significant bit
+        fn code(&mut self, count: u32) -> u32 {
+            return (0..count).fold(0, |code, _| code << 1 | self.bit())
+        }
+
+        fn symbol(&mut self) -> usize {
+            let code = self.code(7);
+            if code < 24 {
+                return 256 + code as usize
+            }
+            let code = code << 1 | self.bit();
+            match code {
+                0x30..=0xbf => return (code - 0x30) as usize,
+                0xc0..=0xc7 => return (code - 0xc0 + 280) as usize,
+                _ => {}
+            }
+            return (code << 1 | self.bit()) as usize - 0x190 + 144
+        }
+    }
+
+    // Decoder for the single fixed Huffman block the encoder writes
+    fn zlib_decompress(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data[..2], [0x78, 0x01]);
+        assert_eq!(((data[0] as u32) << 8 | data[1] as u32) % 31, 0);
+        let mut reader = BitReader { bytes: &data[2..], pos: 0 };
+        assert_eq!(reader.bits(1), 1);
+        assert_eq!(reader.bits(2), 1);
+
+        let mut result: Vec<u8> = Vec::new();
+        loop {
+            let symbol = reader.symbol();
+            if symbol < 256 {
+                result.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                break;
+            }
+            let code = symbol - 257;
+            let length = LENGTH_BASES[code] + reader.bits(LENGTH_EXTRA_BITS[code]) as usize;
+            let code = reader.code(5) as usize;
+            let distance = DISTANCE_BASES[code] + reader.bits(DISTANCE_EXTRA_BITS[code]) as usize;
+            assert!(distance <= result.len() && distance <= WINDOW_SIZE);
+            for _ in 0..length {
+                result.push(result[result.len() - distance]);
+            }
+        }
+
+        let end = 2 + reader.pos.div_ceil(8);
+        assert_eq!(data.len(), end + 4);
+        assert_eq!(data[end..], adler32(&result).to_be_bytes());
+        return result
+    }
+
+    fn pseudo_random(length: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        return (0..length).map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn round_trips_empty_and_short_data() {
+        for data in [&b""[..], b"a", b"ab", b"abc", b"aaaa"] {
+            assert_eq!(zlib_decompress(&zlib_compress(data)), data);
+        }
+    }
+
+    #[test]
+    fn round_trips_random_data_with_every_literal() {
+        let data = pseudo_random(100_000, 1);
+        assert_eq!(zlib_decompress(&zlib_compress(&data)), data);
+    }
+
+    #[test]
+    fn round_trips_repetitive_data_past_the_window() {
+        // Long runs, repeats at distances up to the window size and beyond it
+        let mut data = vec![0u8; 70_000];
+        let block = pseudo_random(40_000, 2);
+        data.extend(&block);
+        data.extend(&block);
+        data.extend((0..50_000).map(|i| (i % 7) as u8));
+        let compressed = zlib_compress(&data);
+        assert!(compressed.len() < data.len() / 2);
+        assert_eq!(zlib_decompress(&compressed), data);
+    }
+}
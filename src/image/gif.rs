@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use crate::image::raster::Image;
+
+const MAX_CODE: u16 = 4096;
+const MAX_SUB_BLOCK: usize = 255;
+
+// Writes the frames as a looping GIF animation showing each frame for delay
+// hundredths of a second. All frames share the palette and size of the first;
+// GIF limits both dimensions to 65535 pixels.
+pub fn write(frames: &[Image], delay: u16) -> Vec<u8> {
+    let mut result = b"GIF89a".to_vec();
+    let (width, height) = frames.first().map_or((0, 0), |f| (f.width as u16, f.height as u16));
+    let palette = frames.first().map_or(Vec::new(), |f| f.palette.clone());
+
+    // The color table has 2^(size + 1) entries, at least 2
+    let mut size = 0;
+    while (2usize << size) < palette.len() {
+        size += 1;
+    }
+    result.extend(&width.to_le_bytes());
+    result.extend(&height.to_le_bytes());
+    result.extend(&[0xf0 | size as u8, 0, 0]);
+    for i in 0..(2usize << size) {
+        result.extend(palette.get(i).unwrap_or(&[0, 0, 0]));
+    }
+
+    // Netscape extension to loop forever
+    result.extend(&[0x21, 0xff, 0x0b]);
+    result.extend(b"NETSCAPE2.0");
+    result.extend(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    let min_code_size = (size + 1).max(2);
+    for frame in frames {
+        result.extend(&[0x21, 0xf9, 0x04, 0x00]);
+        result.extend(&delay.to_le_bytes());
+        result.extend(&[0x00, 0x00]);
+
+        result.push(0x2c);
+        result.extend(&[0, 0, 0, 0]);
+        result.extend(&width.to_le_bytes());
+        result.extend(&height.to_le_bytes());
+        result.push(0);
+
+        result.push(min_code_size as u8);
+        for block in lzw_compress(&frame.pixels, min_code_size).chunks(MAX_SUB_BLOCK) {
+            result.push(block.len() as u8);
+            result.extend(block);
+        }
+        result.push(0);
+    }
+
+    result.push(0x3b);
+    return result
+}
+
+// GIF's LZW variant: codes grow from min_code_size + 1 up to 12 bits and the
+// table is reset with a clear code when it is full.
+fn lzw_compress(pixels: &[u8], min_code_size: u32) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = CodeWriter { bytes: Vec::new(), buffer: 0, bit_count: 0 };
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+
+    writer.write(clear, code_size);
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(pixel) => *pixel as u16,
+        None => {
+            writer.write(end, code_size);
+            return writer.finish()
+        }
+    };
+
+    for pixel in pixels {
+        if let Some(code) = table.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+        if next_code == MAX_CODE {
+            writer.write(clear, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end + 1;
+        } else {
+            if next_code == 1 << code_size {
+                code_size += 1;
+            }
+            table.insert((prefix, *pixel), next_code);
+            next_code += 1;
+        }
+        prefix = *pixel as u16;
+    }
+
+    writer.write(prefix, code_size);
+    writer.write(end, code_size);
+    return writer.finish()
+}
+
+struct CodeWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bit_count: u32
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, bits: u32) {
+        self.buffer |= (code as u32) << self.bit_count;
+        self.bit_count += bits;
+        while self.bit_count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        return self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Decoder for GIF's LZW variant, the table grows one entry behind the encoder
+    fn lzw_decompress(data: &[u8], min_code_size: u32) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let mut pos = 0;
+        let mut read = |bits: u32| {
+            let code = (0..bits as usize).fold(0, |code, i| {
+                let bit = (data[(pos + i) / 8] >> ((pos + i) % 8)) & 1;
+                code | (bit as usize) << i
+            });
+            pos += bits as usize;
+            code
+        };
+
+        let mut result = Vec::new();
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<usize> = None;
+        loop {
+            let code = read(code_size);
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end {
+                return result
+            }
+
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(previous) => {
+                    let mut entry = table[previous].clone();
+                    let first = if code < table.len() { table[code][0] } else { entry[0] };
+                    entry.push(first);
+                    table.push(entry);
+                    table[code].clone()
+                }
+            };
+            result.extend(&entry);
+            previous = Some(code);
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        }
+    }
+
+    fn pseudo_random(length: usize, colors: u32, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        return (0..length).map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((state >> 16) % colors) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn lzw_round_trips_short_data() {
+        for pixels in [&[][..], &[0], &[1, 1], &[0, 1, 0, 1, 0, 1, 1, 1]] {
+            assert_eq!(lzw_decompress(&lzw_compress(pixels, 2), 2), pixels);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips_through_table_resets() {
+        // Enough random pixels to fill the 4096 entry table several times
+        for (colors, min_code_size) in [(2, 2), (4, 2), (16, 4), (256, 8)] {
+            let pixels = pseudo_random(200_000, colors, colors);
+            assert_eq!(lzw_decompress(&lzw_compress(&pixels, min_code_size), min_code_size), pixels);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips_long_runs() {
+        let mut pixels = vec![0u8; 100_000];
+        pixels.extend((0..100_000).map(|i| (i / 1000 % 2) as u8));
+        assert_eq!(lzw_decompress(&lzw_compress(&pixels, 2), 2), pixels);
+    }
+
+    #[test]
+    fn writes_frames_with_their_size_and_trailer() {
+        let frame = Image { width: 3, height: 2, palette: vec![[0, 0, 0], [255, 255, 255]], pixels: vec![0, 1, 0, 1, 0, 1] };
+        let data = write(&[frame.clone(), frame], 10);
+        assert_eq!(&data[..6], b"GIF89a");
+        assert_eq!(data[6..10], [3, 0, 2, 0]);
+        assert_eq!(data.last(), Some(&0x3b));
+    }
+}
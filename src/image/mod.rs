@@ -0,0 +1,7 @@
+mod deflate;
+pub mod gif;
+pub mod netpbm;
+pub mod png;
+pub mod raster;
+pub mod svg;
+pub mod y4m;
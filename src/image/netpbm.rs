@@ -0,0 +1,357 @@
+use std::fmt;
+
+use crate::grid::Rule;
+use crate::rle::RLE;
+
+// Netpbm bitmaps: P1 to P3 are plain text, P4 to P6 binary versions of the same
+// bitmap (PBM), graymap (PGM) and pixmap (PPM) formats. Dark pixels become live
+// cells, in PBM files a set bit is black.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNetpbmError {
+    pub offset: usize,
+    pub reason: &'static str
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImportOptions {
+    // Cells whose pixels are on average darker than this (0.0 is black, 1.0
+    // white) are alive
+    pub threshold: f64,
+    // Size of the square of pixels that makes up one cell
+    pub scale: usize
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl Default for ImportOptions {
+    fn default() -> ImportOptions {
+        ImportOptions {
+            threshold: 0.5,
+            scale: 1
+        }
+    }
+}
+
+pub fn parse(data: &[u8], options: &ImportOptions) -> Result<RLE, ParseNetpbmError> {
+    let (width, height, brightness) = read_pixels(data)?;
+    let scale = options.scale.max(1);
+    let (cell_width, cell_height) = (width / scale, height / scale);
+
+    let mut cells: Vec<(i64, i64)> = Vec::new();
+    for row in 0..cell_height {
+        for col in 0..cell_width {
+            let mut sum = 0.0;
+            for y in row * scale..(row + 1) * scale {
+                for x in col * scale..(col + 1) * scale {
+                    sum += brightness[y * width + x];
+                }
+            }
+            if sum / ((scale * scale) as f64) < options.threshold {
+                cells.push((row as i64, col as i64));
+            }
+        }
+    }
+
+    let bounds = (0, 0, cell_height as i64 - 1, cell_width as i64 - 1);
+    return Ok(RLE::from_cells_in(&cells, bounds, &Rule::default()))
+}
+
+// Writes the pattern's box with every cell as a square of scale pixels, as a
+// plain (P1) or binary (P4) PBM.
+pub fn write_pbm(rle: &RLE, scale: usize, plain: bool) -> Vec<u8> {
+    let scale = scale.max(1);
+    let (width, height) = (rle.width() * scale, rle.height() * scale);
+    let (top, left) = rle.origin();
+    let mut rows = vec![vec![false; width]; height];
+    for (row, col) in rle.get_live_cells() {
+        let (y, x) = ((row - top) as usize * scale, (col - left) as usize * scale);
+        for pixel_row in &mut rows[y..y + scale] {
+            for pixel in &mut pixel_row[x..x + scale] {
+                *pixel = true;
+            }
+        }
+    }
+
+    let mut result = format!("{}\n{} {}\n", if plain { "P1" } else { "P4" }, width, height).into_bytes();
+    for pixels in rows {
+        if plain {
+            // Lines of a plain PBM should be at most 70 characters long
+            for chunk in pixels.chunks(35) {
+                let line: Vec<&str> = chunk.iter().map(|p| if *p { "1" } else { "0" }).collect();
+                result.extend(line.join(" ").bytes());
+                result.push(b'\n');
+            }
+        } else {
+            for chunk in pixels.chunks(8) {
+                let mut byte = 0u8;
+                for (i, pixel) in chunk.iter().enumerate() {
+                    if *pixel {
+                        byte |= 0x80 >> i;
+                    }
+                }
+                result.push(byte);
+            }
+        }
+    }
+    return result
+}
+
+// Returns width, height and the brightness of every pixel from 0.0 to 1.0.
+fn read_pixels(data: &[u8]) -> Result<(usize, usize, Vec<f64>), ParseNetpbmError> {
+    let mut reader = Reader { data, pos: 0 };
+    if data.len() < 2 || data[0] != b'P' || !(b'1'..=b'6').contains(&data[1]) {
+        return Err(error(0, "not a Netpbm image"))
+    }
+    let kind = data[1] - b'0';
+    reader.pos = 2;
+
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max_value = if kind == 1 || kind == 4 { 1 } else { reader.number()? };
+    if max_value == 0 || max_value > 65535 {
+        return Err(error(reader.pos, "maximum value out of range"))
+    }
+    let pixel_count = match width.checked_mul(height) {
+        Some(count) => count,
+        None => return Err(error(reader.pos, "image too large"))
+    };
+    if kind >= 4 {
+        // Exactly one whitespace character separates the header from the raster
+        reader.pos += 1;
+    }
+
+    // Check the size in the header against the data before allocating for it,
+    // plain samples take at least one character each
+    let channels = if kind == 3 || kind == 6 { 3 } else { 1 };
+    let sample_bytes = if kind >= 5 && max_value > 255 { 2 } else { 1 };
+    let raster_bytes = match kind {
+        4 => width.div_ceil(8).checked_mul(height),
+        _ => pixel_count.checked_mul(channels * sample_bytes)
+    };
+    match raster_bytes {
+        Some(bytes) if bytes <= data.len().saturating_sub(reader.pos) => {},
+        _ => return Err(error(data.len(), "unexpected end of image"))
+    }
+
+    let mut brightness = Vec::with_capacity(pixel_count);
+    match kind {
+        1 => {
+            for _ in 0..pixel_count {
+                reader.skip_whitespace();
+                match reader.byte()? {
+                    b'0' => brightness.push(1.0),
+                    b'1' => brightness.push(0.0),
+                    _ => return Err(error(reader.pos - 1, "expected 0 or 1"))
+                }
+            }
+        },
+        4 => {
+            let row_bytes = width.div_ceil(8);
+            for _ in 0..height {
+                let row = reader.bytes(row_bytes)?;
+                for x in 0..width {
+                    let set = row[x / 8] & (0x80 >> (x % 8)) != 0;
+                    brightness.push(if set { 0.0 } else { 1.0 });
+                }
+            }
+        },
+        _ => {
+            for _ in 0..pixel_count {
+                let mut samples = [0.0; 3];
+                for sample in samples.iter_mut().take(channels) {
+                    let value = if kind <= 3 { reader.number()? } else { reader.sample(max_value)? };
+                    if value > max_value {
+                        return Err(error(reader.pos, "sample larger than the maximum value"))
+                    }
+                    *sample = value as f64 / max_value as f64;
+                }
+                brightness.push(match channels {
+                    3 => 0.299 * samples[0] + 0.587 * samples[1] + 0.114 * samples[2],
+                    _ => samples[0]
+                });
+            }
+        }
+    }
+    return Ok((width, height, brightness))
+}
+
+impl<'a> Reader<'a> {
+    // Whitespace and comments from '#' to the end of the line.
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.data.len() {
+            match self.data[self.pos] {
+                b'#' => {
+                    while self.pos < self.data.len() && self.data[self.pos] != b'\n' {
+                        self.pos += 1;
+                    }
+                },
+                c if c.is_ascii_whitespace() => self.pos += 1,
+                _ => return
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, ParseNetpbmError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+        if start == self.data.len() {
+            return Err(error(start, "unexpected end of image"))
+        }
+        if start == self.pos {
+            return Err(error(start, "expected a number"))
+        }
+        match std::str::from_utf8(&self.data[start..self.pos]).unwrap().parse::<usize>() {
+            Ok(n) => Ok(n),
+            Err(_) => Err(error(start, "number too large"))
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, ParseNetpbmError> {
+        return Ok(self.bytes(1)?[0])
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], ParseNetpbmError> {
+        if self.data.len() - self.pos.min(self.data.len()) < count {
+            return Err(error(self.data.len(), "unexpected end of image"))
+        }
+        let bytes = &self.data[self.pos..self.pos + count];
+        self.pos += count;
+        return Ok(bytes)
+    }
+
+    // Binary samples are one byte, or two big-endian bytes above 255.
+    fn sample(&mut self, max_value: usize) -> Result<usize, ParseNetpbmError> {
+        if max_value < 256 {
+            return Ok(self.byte()? as usize)
+        }
+        let bytes = self.bytes(2)?;
+        return Ok((bytes[0] as usize) << 8 | bytes[1] as usize)
+    }
+}
+
+fn error(offset: usize, reason: &'static str) -> ParseNetpbmError {
+    return ParseNetpbmError {
+        offset,
+        reason
+    }
+}
+
+impl fmt::Display for ParseNetpbmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.reason)
+    }
+}
+
+impl std::error::Error for ParseNetpbmError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(data: &[u8]) -> Vec<(i64, i64)> {
+        let mut cells = parse(data, &ImportOptions::default()).unwrap().get_live_cells();
+        cells.sort_unstable();
+        return cells
+    }
+
+    fn parse_error(data: &[u8]) -> ParseNetpbmError {
+        return parse(data, &ImportOptions::default()).unwrap_err()
+    }
+
+    fn with_raster(header: &str, raster: &[u8]) -> Vec<u8> {
+        let mut data = header.as_bytes().to_vec();
+        data.extend(raster);
+        return data
+    }
+
+    const T: [(i64, i64); 4] = [(0, 1), (1, 0), (1, 1), (1, 2)];
+
+    #[test]
+    fn reads_plain_bitmaps() {
+        assert_eq!(cells(b"P1\n# a T\n3 2 # width and height\n0 1 0\n1 1 1\n"), T);
+        // Plain bitmaps don't need whitespace between the pixels
+        assert_eq!(cells(b"P1 3 2 010111"), T);
+    }
+
+    #[test]
+    fn reads_plain_graymaps_and_pixmaps() {
+        assert_eq!(cells(b"P2\n3 2\n# comment before the maximum\n255\n255 0 200\n10 100 127\n"), T);
+        assert_eq!(cells(b"P2\n3 2\n1000\n1000 0 1000\n0 0 0\n"), T);
+        // Pure blue is dark, yellow is bright
+        assert_eq!(cells(b"P3\n3 2\n255\n255 255 0  0 0 255  255 255 255\n0 0 0  255 0 0  0 0 0\n"), T);
+    }
+
+    #[test]
+    fn reads_binary_bitmaps() {
+        assert_eq!(cells(&with_raster("P4\n# a T\n3 2\n", &[0b0100_0000, 0b1110_0000])), T);
+        // Rows start on a new byte
+        let wide = with_raster("P4\n9 1\n", &[0b1000_0000, 0b1000_0000]);
+        assert_eq!(cells(&wide), vec![(0, 0), (0, 8)]);
+    }
+
+    #[test]
+    fn reads_binary_graymaps_and_pixmaps() {
+        assert_eq!(cells(&with_raster("P5\n3 2\n255\n", &[255, 0, 255, 0, 0, 0])), T);
+        // Two big-endian bytes per sample above 255
+        let wide = with_raster("P5\n3 2\n65535\n", &[255, 255, 0, 16, 255, 255, 0, 0, 0, 0, 127, 255]);
+        assert_eq!(cells(&wide), T);
+        let pixmap = with_raster("P6\n# comment\n2 1\n1000\n", &[3, 232, 3, 232, 3, 232, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(cells(&pixmap), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn rejects_truncated_and_bad_images() {
+        assert_eq!(parse_error(b"P1\n3 2\n0 1 0\n1 1").reason, "unexpected end of image");
+        assert_eq!(parse_error(b"P2\n3 2\n255\n0 0 0\n0 0").reason, "unexpected end of image");
+        assert_eq!(parse_error(&with_raster("P4\n3 2\n", &[0])).reason, "unexpected end of image");
+        assert_eq!(parse_error(&with_raster("P5\n3 2\n65535\n", &[0; 11])).reason, "unexpected end of image");
+        assert_eq!(parse_error(&with_raster("P6\n1 1\n255\n", &[0, 0])).reason, "unexpected end of image");
+        // The header alone can't make the reader allocate a huge raster
+        assert_eq!(parse_error(b"P4\n100000 100000\n\0").reason, "unexpected end of image");
+
+        assert_eq!(parse_error(b"P7\n1 1\n0").reason, "not a Netpbm image");
+        assert_eq!(parse_error(b"P2\n1 1\n0\n0").reason, "maximum value out of range");
+        assert_eq!(parse_error(b"P2\n1 1\n3\n4\n").reason, "sample larger than the maximum value");
+        assert_eq!(parse_error(b"P1\n1 1\n2\n").reason, "expected 0 or 1");
+        assert_eq!(parse_error(b"P1\nx 1\n0"), error(3, "expected a number"));
+    }
+
+    #[test]
+    fn scales_and_thresholds_pixels() {
+        // Each cell is a 2x2 block, the left one is half dark
+        let data = b"P2\n4 2\n255\n0 255 0 0\n0 255 0 0\n";
+        let options = |threshold| ImportOptions { threshold, scale: 2 };
+        assert_eq!(parse(data, &options(0.5)).unwrap().get_live_cells(), vec![(0, 1)]);
+        let mut cells = parse(data, &options(0.6)).unwrap().get_live_cells();
+        cells.sort_unstable();
+        assert_eq!(cells, vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn written_bitmaps_read_back() {
+        let rle: RLE = "#P 4 7\nx = 3, y = 2\nbo$3o!".parse().unwrap();
+        for plain in [true, false] {
+            for scale in [1, 3] {
+                let data = write_pbm(&rle, scale, plain);
+                assert!(data.starts_with(if plain { b"P1\n" } else { b"P4\n" }));
+                let read = parse(&data, &ImportOptions { threshold: 0.5, scale }).unwrap();
+                assert_eq!((read.width(), read.height()), (3, 2));
+                let mut cells = read.get_live_cells();
+                cells.sort_unstable();
+                assert_eq!(cells, T);
+            }
+        }
+
+        // Plain lines stay within 70 characters
+        let wide = RLE::from_cells(&[(0, 0), (0, 99)], &Rule::default());
+        let data = write_pbm(&wide, 1, true);
+        assert!(String::from_utf8(data).unwrap().lines().all(|line| line.len() <= 70));
+    }
+}
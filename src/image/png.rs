@@ -0,0 +1,80 @@
+use crate::image::deflate::zlib_compress;
+use crate::image::raster::Image;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+// Writes the image as an 8 bit palette PNG. PNG has no empty images, those
+// become a single pixel of the first color.
+pub fn write(image: &Image) -> Vec<u8> {
+    let mut result = SIGNATURE.to_vec();
+    let (width, height) = (image.width.max(1), image.height.max(1));
+    let pixels: &[u8] = if image.width == 0 || image.height == 0 { &[0] } else { &image.pixels };
+
+    let mut header = Vec::new();
+    header.extend(&(width as u32).to_be_bytes());
+    header.extend(&(height as u32).to_be_bytes());
+    // Bit depth 8, palette color, default compression, filter and no interlacing
+    header.extend(&[8, 3, 0, 0, 0]);
+    write_chunk(&mut result, b"IHDR", &header);
+
+    let palette: Vec<u8> = image.palette.iter().flatten().cloned().collect();
+    write_chunk(&mut result, b"PLTE", &palette);
+
+    // Every row starts with its filter type, always 0 (none)
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width).take(height) {
+        raw.push(0);
+        raw.extend(row);
+    }
+    write_chunk(&mut result, b"IDAT", &zlib_compress(&raw));
+    write_chunk(&mut result, b"IEND", &[]);
+    return result
+}
+
+fn write_chunk(result: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    result.extend(&(data.len() as u32).to_be_bytes());
+    result.extend(kind);
+    result.extend(data);
+    let crc = crc32(kind.iter().chain(data));
+    result.extend(&crc.to_be_bytes());
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    return !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_header_with_the_image_size() {
+        let image = Image { width: 3, height: 2, palette: vec![[0, 0, 0], [255, 255, 255]], pixels: vec![0, 1, 0, 1, 0, 1] };
+        let data = write(&image);
+        assert_eq!(data[..8], SIGNATURE);
+        assert_eq!(&data[12..16], b"IHDR");
+        assert_eq!(data[16..24], [0, 0, 0, 3, 0, 0, 0, 2]);
+        let crc = crc32(data[12..29].iter());
+        assert_eq!(data[29..33], crc.to_be_bytes());
+    }
+
+    #[test]
+    fn writes_empty_image_as_single_pixel() {
+        let image = Image { width: 0, height: 0, palette: vec![[0, 0, 0]], pixels: Vec::new() };
+        let data = write(&image);
+        assert_eq!(data[16..24], [0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn computes_standard_crc() {
+        assert_eq!(crc32(b"123456789".iter()), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND".iter()), 0xae42_6082);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::grid::{Grid, Region};
+
+// CPU renderer producing palette images, so pictures and animations can be
+// made without a window or OpenGL context.
+
+pub type Color = [u8; 3];
+
+// Color of each state starting at dead, states past the end use the last color.
+// Has 1 to 256 colors so every state is a valid palette index in an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    colors: Vec<Color>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub palette: Vec<Color>,
+    // One palette index per pixel, row by row
+    pub pixels: Vec<u8>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub cell_size: usize,
+    pub palette: Palette
+}
+
+impl Palette {
+    // Without colors this is the default palette, colors past 256 are dropped.
+    pub fn new(mut colors: Vec<Color>) -> Palette {
+        if colors.is_empty() {
+            return Palette::default()
+        }
+        colors.truncate(256);
+        return Palette { colors }
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        return &self.colors
+    }
+
+    pub fn index(&self, state: u8) -> u8 {
+        return (state as usize).min(self.colors.len() - 1) as u8
+    }
+
+    pub fn color(&self, state: u8) -> Color {
+        return self.colors[self.index(state) as usize]
+    }
+}
+
+impl Default for Palette {
+    // The colors of the interactive window
+    fn default() -> Palette {
+        Palette {
+            colors: vec![[51, 77, 77], [255, 128, 51]]
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            cell_size: 4,
+            palette: Palette::default()
+        }
+    }
+}
+
+pub fn render(grid: &Grid, region: Region, options: &RenderOptions) -> Image {
+    let (min_row, min_col, max_row, max_col) = region;
+    let cell_size = options.cell_size.max(1);
+    let rows = (max_row - min_row + 1).max(0) as usize;
+    let cols = (max_col - min_col + 1).max(0) as usize;
+
+    let mut image = Image {
+        width: cols * cell_size,
+        height: rows * cell_size,
+        pixels: vec![0; cols * cell_size * rows * cell_size],
+        palette: options.palette.colors().to_vec()
+    };
+    for (row, col, state) in grid.get_live_cell_states() {
+        if row < min_row || row > max_row || col < min_col || col > max_col {
+            continue;
+        }
+        let index = options.palette.index(state);
+        let (y, x) = ((row - min_row) as usize * cell_size, (col - min_col) as usize * cell_size);
+        for pixel_row in y..y + cell_size {
+            let start = pixel_row * image.width + x;
+            for pixel in &mut image.pixels[start..start + cell_size] {
+                *pixel = index;
+            }
+        }
+    }
+    return image
+}
+
+// Renders generations first to last (inclusive) of the grid, one frame every
+// step generations. Without a region all frames show the bounding box of every
+// live cell over the whole range, the generations are then run twice so only
+// one copy of the grid is kept at a time.
+pub fn render_generations(grid: &Grid, first: usize, last: usize, step: usize, region: Option<Region>, options: &RenderOptions) -> Vec<Image> {
+    let region = region.unwrap_or_else(|| {
+        let mut bounds: Option<Region> = None;
+        visit_generations(grid, first, last, step, |g| {
+            if let Some(b) = g.get_bounding_box() {
+                bounds = Some(match bounds {
+                    Some(a) => (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)),
+                    None => b
+                });
+            }
+        });
+        bounds.unwrap_or((0, 0, 0, 0))
+    });
+
+    let mut frames = Vec::new();
+    visit_generations(grid, first, last, step, |g| frames.push(render(g, region, options)));
+    return frames
+}
+
+fn visit_generations(grid: &Grid, first: usize, last: usize, step: usize, mut visit: impl FnMut(&Grid)) {
+    let step = step.max(1);
+    let mut grid = grid.clone();
+    for _ in 0..first {
+        grid.calc_next_generation();
+    }
+
+    let mut generation = first;
+    while generation <= last {
+        visit(&grid);
+        generation += step;
+        if generation > last {
+            break;
+        }
+        for _ in 0..step {
+            grid.calc_next_generation();
+        }
+    }
+}
+
+// Renders a width x height image of the cells around center (row, col) with
+// every pixel covering cells_per_pixel cells in each direction. When zoomed
+// out a pixel takes the color of any live cell it covers.
+pub fn render_view(grid: &Grid, center: (f64, f64), cells_per_pixel: f64, width: usize, height: usize, palette: &Palette) -> Image {
+    let mut image = Image {
+        width,
+        height,
+        pixels: vec![0; width * height],
+        palette: palette.colors().to_vec()
+    };
+
+    let scale = if cells_per_pixel > 0.0 { cells_per_pixel } else { 1.0 };
+    let top = center.0 - height as f64 / 2.0 * scale;
+    let left = center.1 - width as f64 / 2.0 * scale;
+    // Pixels covered by the cell starting at start, one pixel when zoomed out
+    let span = |start: f64| {
+        if scale >= 1.0 {
+            let pixel = (start / scale).floor();
+            return (pixel, pixel + 1.0)
+        }
+        let first = (start / scale).round();
+        let last = ((start + 1.0) / scale).round().max(first + 1.0);
+        (first, last)
+    };
+
+    for (row, col, state) in grid.get_live_cell_states() {
+        let (y0, y1) = span(row as f64 - top);
+        let (x0, x1) = span(col as f64 - left);
+        if y1 <= 0.0 || x1 <= 0.0 || y0 >= height as f64 || x0 >= width as f64 {
+            continue;
+        }
+
+        let index = palette.index(state);
+        for y in (y0.max(0.0) as usize)..(y1.min(height as f64) as usize) {
+            let start = y * width;
+            for pixel in &mut image.pixels[start + x0.max(0.0) as usize..start + x1.min(width as f64) as usize] {
+                *pixel = index;
+            }
+        }
+    }
+    return image
+}
+
+// Center and cells per pixel that fit the region into width x height pixels.
+pub fn fit_region(region: Region, width: usize, height: usize) -> ((f64, f64), f64) {
+    let (min_row, min_col, max_row, max_col) = region;
+    let rows = (max_row - min_row + 1).max(1) as f64;
+    let cols = (max_col - min_col + 1).max(1) as f64;
+    let center = ((min_row + max_row + 1) as f64 / 2.0, (min_col + max_col + 1) as f64 / 2.0);
+    let cells_per_pixel = (rows / height.max(1) as f64).max(cols / width.max(1) as f64);
+    return (center, cells_per_pixel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blinker() -> Grid {
+        let mut grid = Grid::new();
+        for col in -1..2 {
+            grid.set_cell(0, col, true);
+        }
+        return grid
+    }
+
+    #[test]
+    fn renders_generations_in_the_combined_box() {
+        let options = RenderOptions { cell_size: 1, ..RenderOptions::default() };
+        let frames = render_generations(&blinker(), 0, 4, 1, None, &options);
+        assert_eq!(frames.len(), 5);
+        assert!(frames.iter().all(|frame| (frame.width, frame.height) == (3, 3)));
+        assert_eq!(frames[0].pixels, vec![0, 0, 0, 1, 1, 1, 0, 0, 0]);
+        assert_eq!(frames[1].pixels, vec![0, 1, 0, 0, 1, 0, 0, 1, 0]);
+        assert_eq!(frames[4], frames[0]);
+    }
+
+    #[test]
+    fn renders_every_step_generations_in_a_region() {
+        let options = RenderOptions { cell_size: 2, ..RenderOptions::default() };
+        let frames = render_generations(&blinker(), 1, 6, 2, Some((0, -1, 0, 1)), &options);
+        // Generations 1, 3 and 5 show the vertical phase, a single live cell in the region
+        assert_eq!(frames.len(), 3);
+        for frame in &frames {
+            assert_eq!((frame.width, frame.height), (6, 2));
+            assert_eq!(frame.pixels, vec![0, 0, 1, 1, 0, 0, 0, 0, 1, 1, 0, 0]);
+        }
+        assert!(render_generations(&Grid::new(), 0, 2, 1, None, &options).iter().all(|frame| frame.pixels.iter().all(|p| *p == 0)));
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::grid::{Grid, Region};
+use crate::image::raster::{Color, Palette};
+
+// Vector output for figures: one rectangle per run of live cells in a row,
+// grouped by state, with optional gridlines, row and column numbers and text
+// annotations.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub row: i64,
+    pub col: i64,
+    pub text: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvgOptions {
+    pub cell_size: usize,
+    pub palette: Palette,
+    pub gridlines: Option<Color>,
+    // Numbers every n rows and columns along the top and left edges
+    pub label_interval: Option<usize>,
+    // Outlines the cell and writes the text next to it
+    pub annotations: Vec<Annotation>
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            cell_size: 10,
+            palette: Palette::new(vec![[255, 255, 255], [0, 0, 0]]),
+            gridlines: Some([204, 204, 204]),
+            label_interval: None,
+            annotations: Vec::new()
+        }
+    }
+}
+
+pub fn write(grid: &Grid, region: Region, options: &SvgOptions) -> String {
+    let (min_row, min_col, max_row, max_col) = region;
+    let cell = options.cell_size.max(1) as f64;
+    let rows = (max_row - min_row + 1).max(0);
+    let cols = (max_col - min_col + 1).max(0);
+    let font_size = (cell * 0.8).round().max(6.0);
+    let (left, top) = match options.label_interval {
+        Some(_) => {
+            let digits = min_row.to_string().len().max(max_row.to_string().len());
+            ((font_size * (digits as f64 * 0.6 + 0.5)).ceil(), (font_size * 1.5).ceil())
+        },
+        None => (0.0, 0.0)
+    };
+    let width = left + cols as f64 * cell;
+    let height = top + rows as f64 * cell;
+    let color = |state: u8| hex(options.palette.color(state));
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#, width, height).unwrap();
+    writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#, left, top, cols as f64 * cell, rows as f64 * cell, color(0)).unwrap();
+
+    let mut states: BTreeMap<u8, Vec<(i64, i64)>> = BTreeMap::new();
+    for (row, col, state) in grid.get_live_cell_states() {
+        if row >= min_row && row <= max_row && col >= min_col && col <= max_col {
+            states.entry(state).or_default().push((row, col));
+        }
+    }
+    for (state, mut cells) in states {
+        cells.sort_unstable();
+        writeln!(svg, r#"<g fill="{}">"#, color(state)).unwrap();
+        let mut runs: Vec<(i64, i64, i64)> = Vec::new();
+        for (row, col) in cells {
+            match runs.last_mut() {
+                Some((run_row, start, length)) if *run_row == row && *start + *length == col => *length += 1,
+                _ => runs.push((row, col, 1))
+            }
+        }
+        for (row, col, length) in runs {
+            let (x, y) = (left + (col - min_col) as f64 * cell, top + (row - min_row) as f64 * cell);
+            writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}"/>"#, x, y, length as f64 * cell, cell).unwrap();
+        }
+        writeln!(svg, "</g>").unwrap();
+    }
+
+    if let Some(line_color) = options.gridlines {
+        let mut path = String::new();
+        for i in 0..=cols {
+            write!(path, "M{} {}V{}", left + i as f64 * cell, top, height).unwrap();
+        }
+        for i in 0..=rows {
+            write!(path, "M{} {}H{}", left, top + i as f64 * cell, width).unwrap();
+        }
+        writeln!(svg, r#"<path d="{}" stroke="{}" stroke-width="{}" fill="none"/>"#, path, hex(line_color), (cell / 20.0).max(0.5)).unwrap();
+    }
+
+    if let Some(interval) = options.label_interval {
+        let interval = interval.max(1) as i64;
+        writeln!(svg, r#"<g font-family="sans-serif" font-size="{}" fill="black">"#, font_size).unwrap();
+        for col in (min_col..=max_col).filter(|c| c.rem_euclid(interval) == 0) {
+            let x = left + ((col - min_col) as f64 + 0.5) * cell;
+            writeln!(svg, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, x, font_size, col).unwrap();
+        }
+        for row in (min_row..=max_row).filter(|r| r.rem_euclid(interval) == 0) {
+            let y = (top + ((row - min_row) as f64 + 0.5) * cell + font_size * 0.35).round();
+            writeln!(svg, r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#, (left - font_size * 0.25).round(), y, row).unwrap();
+        }
+        writeln!(svg, "</g>").unwrap();
+    }
+
+    if !options.annotations.is_empty() {
+        writeln!(svg, r#"<g font-family="sans-serif" font-size="{}">"#, font_size).unwrap();
+        for annotation in &options.annotations {
+            let x = left + (annotation.col - min_col) as f64 * cell;
+            let y = top + (annotation.row - min_row) as f64 * cell;
+            writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="red" stroke-width="{}"/>"#, x, y, cell, cell, (cell / 8.0).max(1.0)).unwrap();
+            writeln!(svg, r#"<text x="{}" y="{}" fill="red">{}</text>"#, (x + cell * 1.25).round(), (y + cell * 0.5 + font_size * 0.35).round(), escape(&annotation.text)).unwrap();
+        }
+        writeln!(svg, "</g>").unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    return svg
+}
+
+fn hex(color: Color) -> String {
+    return format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn escape(text: &str) -> String {
+    return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Attributes of every element with the given tag, in document order
+    fn elements(svg: &str, tag: &str) -> Vec<BTreeMap<String, String>> {
+        let open = format!("<{} ", tag);
+        return svg.match_indices(&open).map(|(start, _)| {
+            let end = start + svg[start..].find('>').unwrap();
+            let mut attributes = BTreeMap::new();
+            let mut rest = &svg[start + open.len()..end];
+            while let Some(eq) = rest.find("=\"") {
+                let name = rest[..eq].trim().to_owned();
+                let value_end = eq + 2 + rest[eq + 2..].find('"').unwrap();
+                attributes.insert(name, rest[eq + 2..value_end].to_owned());
+                rest = &rest[value_end + 1..];
+            }
+            attributes
+        }).collect()
+    }
+
+    // Rectangles inside the group of the given fill color
+    fn group_rects(svg: &str, fill: &str) -> Vec<(String, String, String)> {
+        let start = svg.find(&format!("<g fill=\"{}\">", fill)).unwrap();
+        let end = start + svg[start..].find("</g>").unwrap();
+        return elements(&svg[start..end], "rect").into_iter()
+            .map(|r| (r["x"].clone(), r["y"].clone(), r["width"].clone()))
+            .collect()
+    }
+
+    fn glider() -> Grid {
+        let mut grid = Grid::new();
+        for (row, col) in [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+            grid.set_cell(row, col, true);
+        }
+        return grid
+    }
+
+    #[test]
+    fn writes_one_rect_per_live_run() {
+        let svg = write(&glider(), (0, 0, 2, 2), &SvgOptions::default());
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="30" height="30""#));
+        assert!(svg.ends_with("</svg>\n"));
+
+        let rects = elements(&svg, "rect");
+        // The background and one rectangle for each of the three runs
+        assert_eq!(rects.len(), 4);
+        assert_eq!(rects[0]["fill"], "#ffffff");
+        assert_eq!(group_rects(&svg, "#000000"), vec![
+            ("10".to_owned(), "0".to_owned(), "10".to_owned()),
+            ("20".to_owned(), "10".to_owned(), "10".to_owned()),
+            ("0".to_owned(), "20".to_owned(), "30".to_owned())
+        ]);
+
+        let paths = elements(&svg, "path");
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0]["stroke"], "#cccccc");
+    }
+
+    #[test]
+    fn groups_states_and_clips_to_the_region() {
+        let mut grid = glider();
+        grid.set_state(2, 1, 2);
+        grid.set_cell(10, 10, true);
+        let options = SvgOptions { gridlines: None, palette: Palette::new(vec![[0, 0, 0], [255, 0, 0], [0, 0, 255]]), ..SvgOptions::default() };
+        let svg = write(&grid, (0, 0, 2, 2), &options);
+
+        assert_eq!(group_rects(&svg, "#ff0000").len(), 4);
+        assert_eq!(group_rects(&svg, "#0000ff"), vec![("10".to_owned(), "20".to_owned(), "10".to_owned())]);
+        assert!(elements(&svg, "path").is_empty());
+    }
+
+    #[test]
+    fn writes_labels_and_escaped_annotations() {
+        let options = SvgOptions {
+            label_interval: Some(2),
+            annotations: vec![Annotation { row: 1, col: 2, text: "a <b> & \"c\"".to_owned() }],
+            ..SvgOptions::default()
+        };
+        let svg = write(&glider(), (0, 0, 2, 2), &options);
+        let labels: Vec<&str> = svg.lines().filter(|l| l.starts_with("<text") && !l.contains("red")).collect();
+        // Columns 0 and 2, then rows 0 and 2
+        assert_eq!(labels.len(), 4);
+        assert!(svg.contains(">a &lt;b&gt; &amp; &quot;c&quot;</text>"));
+    }
+}
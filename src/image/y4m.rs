@@ -0,0 +1,199 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::cli;
+use crate::grid::{Grid, Region, Rule};
+use crate::image::raster::{self, Color, Image, Palette};
+
+// Uncompressed YUV4MPEG2 video, as read by ffmpeg and most other encoders.
+// Frames are 4:2:0 with BT.601 limited range colors, written as they are
+// rendered so long runs don't have to fit into memory.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Camera {
+    // Always shows the region
+    Fixed(Region),
+    // Fits the bounding box of every frame's live cells
+    Follow
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoOptions {
+    // Rounded down to even numbers as required by 4:2:0 chroma
+    pub width: usize,
+    pub height: usize,
+    pub frame_rate: u32,
+    // A frame is written every step generations
+    pub step: usize,
+    pub camera: Camera,
+    pub palette: Palette
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordOptions {
+    pub pattern: String,
+    pub rule: Option<Rule>,
+    pub generations: usize,
+    // Standard output when not given
+    pub output: Option<String>,
+    pub video: VideoOptions
+}
+
+impl Default for VideoOptions {
+    fn default() -> VideoOptions {
+        VideoOptions {
+            width: 640,
+            height: 480,
+            frame_rate: 30,
+            step: 1,
+            camera: Camera::Follow,
+            palette: Palette::default()
+        }
+    }
+}
+
+// Writes generations 0 to generations of the grid (every step-th one) and
+// returns the number of frames.
+pub fn write<W: Write>(out: &mut W, grid: &Grid, generations: usize, options: &VideoOptions) -> io::Result<usize> {
+    let width = (options.width & !1).max(2);
+    let height = (options.height & !1).max(2);
+    let step = options.step.max(1);
+    writeln!(out, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg", width, height, options.frame_rate.max(1))?;
+
+    let mut grid = grid.clone();
+    let mut region = grid.get_bounding_box().unwrap_or((0, 0, 0, 0));
+    let mut frames = 0;
+    let mut generation = 0;
+    loop {
+        if let Camera::Fixed(fixed) = options.camera {
+            region = fixed;
+        } else if let Some(bounds) = grid.get_bounding_box() {
+            region = bounds;
+        }
+        let (center, cells_per_pixel) = raster::fit_region(region, width, height);
+        let image = raster::render_view(&grid, center, cells_per_pixel, width, height, &options.palette);
+        write_frame(out, &image)?;
+        frames += 1;
+
+        if generation + step > generations {
+            break;
+        }
+        for _ in 0..step {
+            grid.calc_next_generation();
+        }
+        generation += step;
+    }
+    return Ok(frames)
+}
+
+// Loads the pattern and writes the video, returns the number of frames.
+pub fn run_command(options: &RecordOptions) -> Result<usize, String> {
+    let mut grid = Grid::new();
+    cli::load_into(&options.pattern, &mut grid)?;
+    if let Some(rule) = options.rule.clone() {
+        grid.set_rule(rule);
+    }
+
+    let result = match &options.output {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+            let mut out = BufWriter::new(file);
+            write(&mut out, &grid, options.generations, &options.video).and_then(|frames| out.flush().map(|_| frames))
+        },
+        None => {
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            write(&mut out, &grid, options.generations, &options.video).and_then(|frames| out.flush().map(|_| frames))
+        }
+    };
+    let name = options.output.as_deref().unwrap_or("stdout");
+    return result.map_err(|e| format!("{}: {}", name, e))
+}
+
+fn write_frame<W: Write>(out: &mut W, image: &Image) -> io::Result<()> {
+    let colors: Vec<[f64; 3]> = image.palette.iter().map(|c| to_ycbcr(*c)).collect();
+    let (width, height) = (image.width, image.height);
+    let mut frame = Vec::with_capacity(width * height * 3 / 2 + 6);
+    frame.extend(b"FRAME\n");
+    frame.extend(image.pixels.iter().map(|p| colors[*p as usize][0].round() as u8));
+
+    // Each chroma sample is the average of a 2x2 block of pixels, the Cb
+    // plane comes before the Cr plane
+    let mut cr_plane = Vec::with_capacity(width * height / 4);
+    for y in (0..height).step_by(2) {
+        for x in (0..width).step_by(2) {
+            let block = [y * width + x, y * width + x + 1, (y + 1) * width + x, (y + 1) * width + x + 1];
+            let (cb, cr) = block.iter().map(|i| colors[image.pixels[*i] as usize]).fold((0.0, 0.0), |(cb, cr), c| (cb + c[1], cr + c[2]));
+            frame.push((cb / 4.0).round() as u8);
+            cr_plane.push((cr / 4.0).round() as u8);
+        }
+    }
+    frame.extend(cr_plane);
+    return out.write_all(&frame)
+}
+
+fn to_ycbcr(color: Color) -> [f64; 3] {
+    let [r, g, b] = [color[0] as f64 / 255.0, color[1] as f64 / 255.0, color[2] as f64 / 255.0];
+    return [
+        16.0 + 65.481 * r + 128.553 * g + 24.966 * b,
+        128.0 - 37.797 * r - 74.203 * g + 112.0 * b,
+        128.0 + 112.0 * r - 93.786 * g - 18.214 * b
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &[u8] = b"YUV4MPEG2 W8 H6 F30:1 Ip A1:1 C420jpeg\n";
+    // 8x6 luma samples and two 4x3 chroma planes
+    const FRAME_BYTES: usize = 6 + 8 * 6 + 2 * 4 * 3;
+
+    fn blinker() -> Grid {
+        let mut grid = Grid::new();
+        for col in -1..2 {
+            grid.set_cell(0, col, true);
+        }
+        return grid
+    }
+
+    fn frames(video: &[u8]) -> Vec<&[u8]> {
+        assert!(video.starts_with(HEADER));
+        let frames: Vec<&[u8]> = video[HEADER.len()..].chunks(FRAME_BYTES).collect();
+        assert!(frames.iter().all(|frame| frame.len() == FRAME_BYTES && frame.starts_with(b"FRAME\n")));
+        return frames
+    }
+
+    #[test]
+    fn writes_header_and_frames() {
+        let options = VideoOptions { width: 9, height: 6, ..VideoOptions::default() };
+        let mut video = Vec::new();
+        assert_eq!(write(&mut video, &blinker(), 3, &options).unwrap(), 4);
+        assert_eq!(video.len(), HEADER.len() + 4 * FRAME_BYTES);
+        let frames = frames(&video);
+        assert_eq!(frames[0], frames[2]);
+        assert_ne!(frames[0], frames[1]);
+    }
+
+    #[test]
+    fn writes_every_step_generations() {
+        let options = VideoOptions { width: 8, height: 6, step: 2, ..VideoOptions::default() };
+        let mut video = Vec::new();
+        assert_eq!(write(&mut video, &blinker(), 5, &options).unwrap(), 3);
+        let frames = frames(&video);
+        assert!(frames.iter().all(|frame| *frame == frames[0]));
+    }
+
+    #[test]
+    fn empty_regions_have_the_dead_color() {
+        let options = VideoOptions { width: 8, height: 6, camera: Camera::Fixed((100, 100, 101, 101)), ..VideoOptions::default() };
+        let mut video = Vec::new();
+        write(&mut video, &blinker(), 0, &options).unwrap();
+        let frame = frames(&video)[0];
+
+        let [y, cb, cr] = to_ycbcr(options.palette.color(0)).map(|v| v.round() as u8);
+        assert!(frame[6..54].iter().all(|v| *v == y));
+        assert!(frame[54..66].iter().all(|v| *v == cb));
+        assert!(frame[66..].iter().all(|v| *v == cr));
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::apgcode;
+use crate::format::{self, Format, ParsePatternError};
+use crate::grid::{Grid, Rule};
+use crate::rle::RLE;
+
+// An index over a directory tree of pattern files. Every file with a known
+// extension is parsed once while scanning; the entries keep the metadata so
+// searching doesn't touch the disk again, loading a pattern re-reads its file.
+
+const EXTENSIONS: [&str; 4] = ["rle", "cells", "mc", "lif"];
+
+#[derive(Debug)]
+pub enum IndexError {
+    Io(io::Error),
+    Parse(ParsePatternError)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub path: PathBuf,
+    pub format: Format,
+    pub name: String,
+    pub author: String,
+    // Canonical form, Conway's Life when the file doesn't name a rule
+    pub rule: String,
+    pub width: usize,
+    pub height: usize,
+    pub population: usize,
+    pub apgcode: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    // Case-insensitive substring of the name or the file name
+    Name(String),
+    Rule(String),
+    Apgcode(String)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub dir: String,
+    // Entries have to match all queries
+    pub queries: Vec<Query>,
+    // Classify patterns up to this period to find their apgcodes
+    pub max_period: Option<usize>
+}
+
+#[derive(Debug)]
+pub struct PatternIndex {
+    entries: Vec<IndexEntry>,
+    failures: Vec<(PathBuf, IndexError)>
+}
+
+impl PatternIndex {
+    // Classification runs every pattern for up to max_period generations to
+    // find its apgcode, which is slow for large collections; pass None to skip it.
+    // Only an unreadable dir is an error, problems below it end up in failures.
+    pub fn scan(dir: &Path, max_period: Option<usize>) -> io::Result<PatternIndex> {
+        let mut index = PatternIndex {
+            entries: Vec::new(),
+            failures: Vec::new()
+        };
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            index.collect_paths(dir, entry, &mut paths);
+        }
+        paths.sort();
+
+        for path in paths {
+            match IndexEntry::read(&path, max_period) {
+                Ok(entry) => index.entries.push(entry),
+                Err(e) => index.failures.push((path, e))
+            }
+        }
+        return Ok(index)
+    }
+
+    // Symbolic links to files are indexed, links to directories are not
+    // followed so a cycle can't send the scan around in circles.
+    fn collect_paths(&mut self, dir: &Path, entry: io::Result<fs::DirEntry>, paths: &mut Vec<PathBuf>) {
+        let (path, file_type) = match entry.and_then(|e| Ok((e.path(), e.file_type()?))) {
+            Ok(entry) => entry,
+            Err(e) => {
+                self.failures.push((dir.to_owned(), IndexError::Io(e)));
+                return
+            }
+        };
+
+        if file_type.is_dir() {
+            match fs::read_dir(&path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        self.collect_paths(&path, entry, paths);
+                    }
+                },
+                Err(e) => self.failures.push((path, IndexError::Io(e)))
+            }
+            return
+        }
+
+        let extension = path.extension().map_or(String::new(), |e| e.to_string_lossy().to_ascii_lowercase());
+        if EXTENSIONS.contains(&extension.as_str()) && (file_type.is_file() || path.is_file()) {
+            paths.push(path);
+        }
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        return &self.entries
+    }
+
+    // Files that could not be read or parsed while scanning.
+    pub fn failures(&self) -> &[(PathBuf, IndexError)] {
+        return &self.failures
+    }
+
+    // The entry whose name or file name is the given name, or the only one that
+    // contains it.
+    pub fn find(&self, name: &str) -> Result<&IndexEntry, String> {
+        let matches = self.search(&Query::Name(name.to_owned()));
+        let exact = matches.iter().find(|e| {
+            let file_name = e.path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned());
+            e.name.eq_ignore_ascii_case(name) || file_name.eq_ignore_ascii_case(name)
+        });
+
+        match (exact, matches.len()) {
+            (Some(entry), _) => Ok(entry),
+            (None, 0) => Err(format!("no pattern matching '{}' in the index", name)),
+            (None, 1) => Ok(matches[0]),
+            (None, _) => {
+                let names: Vec<&str> = matches.iter().take(10).map(|e| e.name.as_str()).collect();
+                Err(format!("'{}' matches {} patterns, e.g. {}", name, matches.len(), names.join(", ")))
+            }
+        }
+    }
+
+    pub fn search(&self, query: &Query) -> Vec<&IndexEntry> {
+        match query {
+            Query::Name(name) => {
+                let name = name.to_lowercase();
+                self.entries.iter().filter(|e| {
+                    let file_name = e.path.file_stem().map_or(String::new(), |s| s.to_string_lossy().to_lowercase());
+                    e.name.to_lowercase().contains(&name) || file_name.contains(&name)
+                }).collect()
+            },
+            Query::Rule(rule) => {
+                let rule = canonical_rule(rule);
+                self.entries.iter().filter(|e| e.rule == rule).collect()
+            },
+            Query::Apgcode(code) => {
+                self.entries.iter().filter(|e| e.apgcode.as_deref() == Some(code.as_str())).collect()
+            }
+        }
+    }
+
+    // Entries matching every query, in index order.
+    pub fn search_all(&self, queries: &[Query]) -> Vec<&IndexEntry> {
+        let mut entries: Vec<&IndexEntry> = self.entries.iter().collect();
+        for query in queries {
+            // Paths are unique within an index
+            let matches: HashSet<&Path> = self.search(query).into_iter().map(|e| e.path.as_path()).collect();
+            entries.retain(|e| matches.contains(e.path.as_path()));
+        }
+        return entries
+    }
+}
+
+impl IndexEntry {
+    fn read(path: &Path, max_period: Option<usize>) -> Result<IndexEntry, IndexError> {
+        let contents = fs::read_to_string(path)?;
+        let format = format_of(path, &contents);
+        let rle = format::parse_as(&contents, format)?;
+
+        let name = match rle.name() {
+            "" => path.file_stem().map_or(String::new(), |s| s.to_string_lossy().into_owned()),
+            name => name.to_owned()
+        };
+        let apgcode = max_period.and_then(|max_period| {
+            let mut grid = Grid::new();
+            rle.apply(&mut grid);
+            apgcode::encode(&grid, max_period).ok()
+        });
+
+        return Ok(IndexEntry {
+            path: path.to_owned(),
+            format,
+            name,
+            author: rle.author().to_owned(),
+            rule: match rle.rule_name() {
+                Some(name) => name.to_owned(),
+                None => rle.rule().cloned().unwrap_or_default().to_string()
+            },
+            width: rle.width(),
+            height: rle.height(),
+            population: rle.get_live_cells().len(),
+            apgcode
+        })
+    }
+
+    pub fn load(&self) -> Result<RLE, IndexError> {
+        let contents = fs::read_to_string(&self.path)?;
+        return Ok(format::parse_as(&contents, self.format)?)
+    }
+}
+
+// Prints the matching entries as tab separated lines, files that couldn't be
+// indexed are reported on stderr.
+pub fn run_command(options: &SearchOptions) -> Result<(), String> {
+    let index = PatternIndex::scan(Path::new(&options.dir), options.max_period).map_err(|e| format!("{}: {}", options.dir, e))?;
+    for (path, e) in index.failures() {
+        eprintln!("{}: {}", path.display(), e);
+    }
+
+    let entries = index.search_all(&options.queries);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = writeln!(out, "path\tname\trule\twidth\theight\tpopulation\tapgcode").and_then(|_| {
+        for entry in entries {
+            writeln!(out, "{}\t{}\t{}\t{}\t{}\t{}\t{}", entry.path.display(), entry.name, entry.rule,
+                entry.width, entry.height, entry.population, entry.apgcode.as_deref().unwrap_or("-"))?;
+        }
+        Ok(())
+    });
+    return result.map_err(|e| format!("stdout: {}", e))
+}
+
+fn format_of(path: &Path, contents: &str) -> Format {
+    return path.to_str().and_then(format::from_extension).unwrap_or_else(|| format::detect(contents))
+}
+
+// A missing rule means Conway's Life.
+fn canonical_rule(rule: &str) -> String {
+    if rule.trim().is_empty() {
+        return Rule::default().to_string()
+    }
+    match rule.parse::<Rule>() {
+        Ok(rule) => rule.to_string(),
+        Err(_) => rule.trim().to_owned()
+    }
+}
+
+impl From<io::Error> for IndexError {
+    fn from(error: io::Error) -> IndexError {
+        IndexError::Io(error)
+    }
+}
+
+impl From<ParsePatternError> for IndexError {
+    fn from(error: ParsePatternError) -> IndexError {
+        IndexError::Parse(error)
+    }
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexError::Io(e) => write!(f, "{}", e),
+            IndexError::Parse(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl std::error::Error for IndexError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory of pattern files, removed when dropped
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> TestDir {
+            let dir = std::env::temp_dir().join(format!("yagol-index-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            for (path, contents) in files {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            return TestDir(dir)
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const FILES: [(&str, &str); 6] = [
+        ("glider.rle", "#N Glider\n#O Richard K. Guy\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"),
+        ("highlife/blinker.rle", "x = 3, y = 1, rule = B36/S23\n3o!\n"),
+        ("still/block.cells", "!Name: Block\nOO\nOO\n"),
+        ("still/Boat.LIF", "#Life 1.06\n0 0\n1 0\n0 1\n2 1\n1 2\n"),
+        ("broken.rle", "x = 3, y = 3\nbo$2bo$3q!\n"),
+        ("notes.txt", "not a pattern\n")
+    ];
+
+    fn names(entries: &[&IndexEntry]) -> Vec<String> {
+        return entries.iter().map(|e| e.name.clone()).collect()
+    }
+
+    #[test]
+    fn scans_pattern_files_recursively() {
+        let dir = TestDir::new("scan", &FILES);
+        let index = PatternIndex::scan(&dir.0, Some(16)).unwrap();
+
+        // Sorted by path, the text file is skipped and the broken one is a failure
+        assert_eq!(names(&index.entries().iter().collect::<Vec<_>>()), vec!["Glider", "blinker", "Boat", "Block"]);
+        assert_eq!(index.failures().len(), 1);
+        assert_eq!(index.failures()[0].0, dir.0.join("broken.rle"));
+
+        let glider = &index.entries()[0];
+        assert_eq!((glider.format, glider.author.as_str(), glider.rule.as_str()), (Format::Rle, "Richard K. Guy", "B3/S23"));
+        assert_eq!((glider.width, glider.height, glider.population), (3, 3, 5));
+        assert_eq!(glider.apgcode.as_deref(), Some("xq4_153"));
+
+        let boat = &index.entries()[2];
+        assert_eq!((boat.format, boat.rule.as_str(), boat.population), (Format::Life106, "B3/S23", 5));
+        assert_eq!(boat.apgcode.as_deref(), Some("xs5_253"));
+        assert_eq!(index.entries()[3].format, Format::Cells);
+
+        // Without classification there are no apgcodes
+        assert!(PatternIndex::scan(&dir.0, None).unwrap().entries().iter().all(|e| e.apgcode.is_none()));
+        assert!(PatternIndex::scan(&dir.0.join("missing"), None).is_err());
+    }
+
+    #[test]
+    fn searches_by_name_rule_and_apgcode() {
+        let dir = TestDir::new("search", &FILES);
+        let index = PatternIndex::scan(&dir.0, Some(16)).unwrap();
+
+        assert_eq!(names(&index.search(&Query::Name("BL".to_owned()))), vec!["blinker", "Block"]);
+        assert_eq!(names(&index.search(&Query::Rule("b3/s23".to_owned()))), vec!["Glider", "Boat", "Block"]);
+        assert_eq!(names(&index.search(&Query::Rule(String::new()))), vec!["Glider", "Boat", "Block"]);
+        assert_eq!(names(&index.search(&Query::Apgcode("xs4_33".to_owned()))), vec!["Block"]);
+
+        let queries = [Query::Name("b".to_owned()), Query::Rule("B3/S23".to_owned())];
+        assert_eq!(names(&index.search_all(&queries)), vec!["Boat", "Block"]);
+        assert_eq!(index.search_all(&[]).len(), 4);
+    }
+
+    #[test]
+    fn finds_single_patterns() {
+        let dir = TestDir::new("find", &FILES);
+        let index = PatternIndex::scan(&dir.0, None).unwrap();
+
+        assert_eq!(index.find("glider").unwrap().name, "Glider");
+        // An exact name wins over other entries containing it
+        assert_eq!(index.find("block").unwrap().name, "Block");
+        assert_eq!(index.find("blink").unwrap().name, "blinker");
+        assert_eq!(index.find("bl").err().unwrap(), "'bl' matches 2 patterns, e.g. blinker, Block");
+        assert_eq!(index.find("gun").err().unwrap(), "no pattern matching 'gun' in the index");
+
+        let rle = index.find("boat").unwrap().load().unwrap();
+        assert_eq!(rle.get_live_cells().len(), 5);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+
+use crate::cli;
+use crate::grid::{full_hensel_mask, hensel_class, hensel_class_count, Grid, Rule, HENSEL_LETTERS, NEIGHBORHOOD_COUNT, Region};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub birth: bool,
+    pub count: usize,
+    // Index into HENSEL_LETTERS[count], None for outer totalistic transitions
+    pub letter: Option<usize>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InferRuleError {
+    TooFewGenerations(usize),
+    Contradiction {
+        generation: usize,
+        row: i64,
+        col: i64,
+        transition: Transition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InferOptions {
+    // Patterns of successive generations, in order
+    pub files: Vec<String>,
+    pub isotropic: bool
+}
+
+pub struct InferredRule {
+    // Observed outcome of every transition, None if it never occurred
+    transitions: Vec<(Transition, Option<bool>)>
+}
+
+// Finds every life-like (or isotropic) rule that turns each generation into
+// the next one. Cells outside the patterns are assumed to stay dead.
+pub fn infer_rule(generations: &[Grid], isotropic: bool) -> Result<InferredRule, InferRuleError> {
+    if generations.len() < 2 {
+        return Err(InferRuleError::TooFewGenerations(generations.len()))
+    }
+
+    let transitions = all_transitions(isotropic);
+    let mut lookup = vec![0; NEIGHBORHOOD_COUNT];
+    for (neighborhood, index) in lookup.iter_mut().enumerate() {
+        let transition = transition_of(neighborhood, isotropic);
+        *index = transitions.iter().position(|t| *t == transition).unwrap();
+    }
+
+    let mut result = InferredRule {
+        transitions: transitions.into_iter().map(|t| (t, None)).collect()
+    };
+
+    for (generation, pair) in generations.windows(2).enumerate() {
+        let (current, next) = (&pair[0], &pair[1]);
+        let bounds = merge_bounds(current.get_bounding_box(), next.get_bounding_box()).unwrap_or_default();
+
+        // The margin of two guarantees that at least one empty neighborhood is checked
+        for row in (bounds.0 - 2)..=(bounds.2 + 2) {
+            for col in (bounds.1 - 2)..=(bounds.3 + 2) {
+                let index = lookup[current.get_neighborhood(row, col)];
+                let observed = next.get_cell(row, col);
+                let (transition, outcome) = &mut result.transitions[index];
+
+                match outcome {
+                    Some(expected) if *expected != observed => {
+                        return Err(InferRuleError::Contradiction {
+                            generation,
+                            row,
+                            col,
+                            transition: *transition
+                        })
+                    },
+                    _ => *outcome = Some(observed)
+                }
+            }
+        }
+    }
+
+    return Ok(result)
+}
+
+// Loads every file into its own grid at the coordinates stored in the file
+// and infers the rule between them.
+pub fn run_command(options: &InferOptions) -> Result<InferredRule, String> {
+    let mut generations = Vec::new();
+    for file in &options.files {
+        let mut grid = Grid::new();
+        cli::load_into(file, &mut grid)?;
+        generations.push(grid);
+    }
+    return infer_rule(&generations, options.isotropic).map_err(|e| e.to_string())
+}
+
+impl InferredRule {
+    pub fn constrained(&self) -> Vec<(Transition, bool)> {
+        return self.transitions.iter()
+            .filter_map(|(t, outcome)| outcome.map(|o| (*t, o)))
+            .collect()
+    }
+
+    pub fn unconstrained(&self) -> Vec<Transition> {
+        return self.transitions.iter()
+            .filter(|(_, outcome)| outcome.is_none())
+            .map(|(t, _)| *t)
+            .collect()
+    }
+
+    // The rule with only the transitions that were observed to be on.
+    pub fn min_rule(&self) -> Rule {
+        return self.build_rule(|outcome| outcome == Some(true))
+    }
+
+    // The rule with every transition that was not observed to be off.
+    pub fn max_rule(&self) -> Rule {
+        return self.build_rule(|outcome| outcome != Some(false))
+    }
+
+    fn build_rule<F: Fn(Option<bool>) -> bool>(&self, include: F) -> Rule {
+        let mut become_alive = [0u16; 9];
+        let mut stay_alive = [0u16; 9];
+
+        for (transition, outcome) in &self.transitions {
+            if !include(*outcome) {
+                continue;
+            }
+            let masks = if transition.birth { &mut become_alive } else { &mut stay_alive };
+            masks[transition.count] |= match transition.letter {
+                Some(letter) => 1 << letter,
+                None => full_hensel_mask(transition.count)
+            };
+        }
+
+        return Rule::from_hensel_masks(become_alive, stay_alive)
+    }
+}
+
+impl fmt::Display for InferredRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} - {}", self.min_rule(), self.max_rule())?;
+
+        let mut on = Vec::new();
+        let mut off = Vec::new();
+        for (transition, outcome) in self.constrained() {
+            if outcome { on.push(transition) } else { off.push(transition) }
+        }
+
+        write_transitions(f, "on", &on)?;
+        write_transitions(f, "off", &off)?;
+        write_transitions(f, "unconstrained", &self.unconstrained())
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", if self.birth { "B" } else { "S" }, self.count)?;
+        if let Some(letter) = self.letter {
+            if let Some(c) = HENSEL_LETTERS[self.count].chars().nth(letter) {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for InferRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InferRuleError::TooFewGenerations(n) => write!(f, "need at least 2 generations, got {}", n),
+            InferRuleError::Contradiction { generation, row, col, transition } => {
+                write!(f, "no consistent rule: transition {} differs at row {}, col {} after generation {}", transition, row, col, generation)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InferRuleError {}
+
+fn write_transitions(f: &mut fmt::Formatter<'_>, label: &str, transitions: &[Transition]) -> fmt::Result {
+    write!(f, "{}:", label)?;
+    for transition in transitions {
+        write!(f, " {}", transition)?;
+    }
+    writeln!(f)
+}
+
+fn all_transitions(isotropic: bool) -> Vec<Transition> {
+    let mut transitions = Vec::new();
+    for birth in [true, false].iter() {
+        for count in 0..9 {
+            if !isotropic {
+                transitions.push(Transition { birth: *birth, count, letter: None });
+                continue;
+            }
+            for letter in 0..hensel_class_count(count) {
+                transitions.push(Transition { birth: *birth, count, letter: Some(letter) });
+            }
+        }
+    }
+    return transitions
+}
+
+fn transition_of(neighborhood: usize, isotropic: bool) -> Transition {
+    let (count, letter) = hensel_class(neighborhood);
+    return Transition {
+        birth: neighborhood & (1 << 4) == 0,
+        count,
+        letter: if isotropic { Some(letter) } else { None }
+    }
+}
+
+fn merge_bounds(a: Option<Region>, b: Option<Region>) -> Option<Region> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))),
+        (a, None) => a,
+        (None, b) => b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(cells: &[(i64, i64)]) -> Grid {
+        let mut grid = Grid::new();
+        for (row, col) in cells {
+            grid.set_cell(*row, *col, true);
+        }
+        return grid
+    }
+
+    fn glider_generations() -> Vec<Grid> {
+        let first = grid(&[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        let mut second = first.clone();
+        second.calc_next_generation();
+        return vec![first, second]
+    }
+
+    #[test]
+    fn infers_bounds_from_a_glider() {
+        let inferred = infer_rule(&glider_generations(), false).unwrap();
+        assert_eq!(inferred.min_rule(), "B3/S23".parse::<Rule>().unwrap());
+        assert_eq!(inferred.max_rule(), "B34678/S02345678".parse::<Rule>().unwrap());
+        assert_eq!(inferred.unconstrained().iter().map(|t| t.to_string()).collect::<Vec<_>>(), vec!["B4", "B6", "B7", "B8", "S0", "S4", "S5", "S6", "S7", "S8"]);
+        assert!(inferred.to_string().starts_with("B3/S23 - B34678/S02345678\non: B3 S2 S3\noff: B0 B1 B2 B5 S1\n"));
+    }
+
+    #[test]
+    fn infers_isotropic_bounds_from_a_glider() {
+        let inferred = infer_rule(&glider_generations(), true).unwrap();
+        assert_eq!(inferred.min_rule(), "B3in/S2e3nr".parse::<Rule>().unwrap());
+        assert_eq!(inferred.max_rule(), "B2-ea345-n678/S02345678".parse::<Rule>().unwrap());
+    }
+
+    #[test]
+    fn reports_contradictions() {
+        let block = grid(&[(0, 0), (0, 1), (1, 0), (1, 1)]);
+        let error = infer_rule(&[block.clone(), block, Grid::new()], false).err().unwrap();
+        assert_eq!(error, InferRuleError::Contradiction {
+            generation: 1,
+            row: 0,
+            col: 0,
+            transition: Transition { birth: false, count: 3, letter: None }
+        });
+        assert_eq!(error.to_string(), "no consistent rule: transition S3 differs at row 0, col 0 after generation 1");
+    }
+
+    #[test]
+    fn needs_two_generations() {
+        assert_eq!(infer_rule(&glider_generations()[..1], false).err(), Some(InferRuleError::TooFewGenerations(1)));
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::rle::RLE;
+
+// Well-known patterns shipped with the binary. Each one is an RLE file in
+// patterns/ whose #N, #O and #C lines hold the name, author and description.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Category {
+    StillLife,
+    Oscillator,
+    Spaceship,
+    Gun,
+    Methuselah
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListOptions {
+    // All patterns when not given
+    pub category: Option<Category>
+}
+
+#[derive(Debug)]
+pub struct LibraryPattern {
+    id: &'static str,
+    category: Category,
+    rle: &'static str
+}
+
+const PATTERNS: &[LibraryPattern] = &[
+    LibraryPattern { id: "block", category: Category::StillLife, rle: include_str!("../patterns/block.rle") },
+    LibraryPattern { id: "beehive", category: Category::StillLife, rle: include_str!("../patterns/beehive.rle") },
+    LibraryPattern { id: "loaf", category: Category::StillLife, rle: include_str!("../patterns/loaf.rle") },
+    LibraryPattern { id: "boat", category: Category::StillLife, rle: include_str!("../patterns/boat.rle") },
+    LibraryPattern { id: "blinker", category: Category::Oscillator, rle: include_str!("../patterns/blinker.rle") },
+    LibraryPattern { id: "toad", category: Category::Oscillator, rle: include_str!("../patterns/toad.rle") },
+    LibraryPattern { id: "beacon", category: Category::Oscillator, rle: include_str!("../patterns/beacon.rle") },
+    LibraryPattern { id: "pulsar", category: Category::Oscillator, rle: include_str!("../patterns/pulsar.rle") },
+    LibraryPattern { id: "pentadecathlon", category: Category::Oscillator, rle: include_str!("../patterns/pentadecathlon.rle") },
+    LibraryPattern { id: "glider", category: Category::Spaceship, rle: include_str!("../patterns/glider.rle") },
+    LibraryPattern { id: "lwss", category: Category::Spaceship, rle: include_str!("../patterns/lwss.rle") },
+    LibraryPattern { id: "mwss", category: Category::Spaceship, rle: include_str!("../patterns/mwss.rle") },
+    LibraryPattern { id: "hwss", category: Category::Spaceship, rle: include_str!("../patterns/hwss.rle") },
+    LibraryPattern { id: "gosper-glider-gun", category: Category::Gun, rle: include_str!("../patterns/gosper-glider-gun.rle") },
+    LibraryPattern { id: "r-pentomino", category: Category::Methuselah, rle: include_str!("../patterns/r-pentomino.rle") },
+    LibraryPattern { id: "acorn", category: Category::Methuselah, rle: include_str!("../patterns/acorn.rle") },
+    LibraryPattern { id: "diehard", category: Category::Methuselah, rle: include_str!("../patterns/diehard.rle") },
+    LibraryPattern { id: "pi-heptomino", category: Category::Methuselah, rle: include_str!("../patterns/pi-heptomino.rle") }
+];
+
+impl LibraryPattern {
+    pub fn id(&self) -> &'static str {
+        return self.id
+    }
+
+    pub fn category(&self) -> Category {
+        return self.category
+    }
+
+    // The embedded files are known to be valid, a parse error is a bug.
+    pub fn rle(&self) -> RLE {
+        return RLE::from_str(self.rle).unwrap_or_else(|e| panic!("library pattern '{}': {}", self.id, e))
+    }
+
+    pub fn name(&self) -> String {
+        return self.rle().name().to_owned()
+    }
+
+    pub fn author(&self) -> String {
+        return self.rle().author().to_owned()
+    }
+
+    pub fn description(&self) -> String {
+        return self.rle().comments().join(" ")
+    }
+}
+
+pub fn all() -> &'static [LibraryPattern] {
+    return PATTERNS
+}
+
+pub fn by_category(category: Category) -> impl Iterator<Item = &'static LibraryPattern> {
+    return PATTERNS.iter().filter(move |p| p.category == category)
+}
+
+// Looks a pattern up by id or by its full name, ignoring case.
+pub fn get(name: &str) -> Option<&'static LibraryPattern> {
+    let name = name.trim();
+    return PATTERNS.iter().find(|p| p.id.eq_ignore_ascii_case(name))
+        .or_else(|| PATTERNS.iter().find(|p| p.name().eq_ignore_ascii_case(name)))
+}
+
+// Prints the patterns as tab separated lines.
+pub fn run_command(options: &ListOptions) -> Result<(), String> {
+    let patterns: Vec<&LibraryPattern> = match options.category {
+        Some(category) => by_category(category).collect(),
+        None => PATTERNS.iter().collect()
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = writeln!(out, "id\tcategory\tname\tauthor\tdescription").and_then(|_| {
+        for p in patterns {
+            writeln!(out, "{}\t{}\t{}\t{}\t{}", p.id(), p.category(), p.name(), p.author(), p.description())?;
+        }
+        Ok(())
+    });
+    return result.map_err(|e| format!("stdout: {}", e))
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Category::StillLife => write!(f, "still-life"),
+            Category::Oscillator => write!(f, "oscillator"),
+            Category::Spaceship => write!(f, "spaceship"),
+            Category::Gun => write!(f, "gun"),
+            Category::Methuselah => write!(f, "methuselah")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apgcode;
+    use crate::grid::{Grid, Rule};
+
+    const CELL_COUNTS: [(&str, usize); 18] = [
+        ("block", 4), ("beehive", 6), ("loaf", 7), ("boat", 5),
+        ("blinker", 3), ("toad", 6), ("beacon", 8), ("pulsar", 48), ("pentadecathlon", 12),
+        ("glider", 5), ("lwss", 9), ("mwss", 11), ("hwss", 13),
+        ("gosper-glider-gun", 36),
+        ("r-pentomino", 5), ("acorn", 7), ("diehard", 7), ("pi-heptomino", 7)
+    ];
+
+    #[test]
+    fn every_pattern_parses_with_its_cell_count() {
+        assert_eq!(all().len(), CELL_COUNTS.len());
+        for (pattern, (id, count)) in all().iter().zip(CELL_COUNTS) {
+            assert_eq!(pattern.id(), id);
+            let rle = pattern.rle();
+            assert_eq!(rle.get_live_cells().len(), count, "{}", id);
+            assert_eq!(rle.rule(), Some(&Rule::default()), "{}", id);
+            assert!(!pattern.name().is_empty() && !pattern.description().is_empty(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn categories_match_the_patterns() {
+        // Still lifes, oscillators and spaceships have the matching apgcode prefix
+        for (category, prefix) in [(Category::StillLife, "xs"), (Category::Oscillator, "xp"), (Category::Spaceship, "xq")] {
+            for pattern in by_category(category) {
+                let mut grid = Grid::new();
+                pattern.rle().apply(&mut grid);
+                let code = apgcode::encode(&grid, 30).unwrap();
+                assert!(code.starts_with(prefix), "{} is {}", pattern.id(), code);
+            }
+        }
+        assert_eq!(by_category(Category::Gun).map(|p| p.id()).collect::<Vec<_>>(), vec!["gosper-glider-gun"]);
+        assert_eq!(by_category(Category::Methuselah).count(), 4);
+    }
+
+    #[test]
+    fn finds_patterns_by_id_or_name() {
+        assert_eq!(get("LWSS").map(|p| p.id()), Some("lwss"));
+        assert_eq!(get(" r-pentomino ").map(|p| p.id()), Some("r-pentomino"));
+        let gun = get("gosper-glider-gun").unwrap();
+        assert_eq!(get(&gun.name().to_uppercase()).map(|p| p.id()), Some("gosper-glider-gun"));
+        assert!(get("spaceship").is_none());
+    }
+}
@@ -0,0 +1,272 @@
+use std::fmt;
+
+use crate::grid::Rule;
+use crate::rle::RLE;
+
+// Readers and writers for the Life 1.05 and Life 1.06 formats. Both use x/y
+// coordinates, which map to the grid's col/row.
+
+pub const LIFE_105_HEADER: &str = "#Life 1.05";
+pub const LIFE_106_HEADER: &str = "#Life 1.06";
+
+// Wider patterns are written as several #P blocks side by side so no line is
+// longer than 80 characters
+const MAX_BLOCK_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLifeError {
+    pub line: usize,
+    pub column: usize,
+    pub text: String
+}
+
+// Life 1.05 stores "#D" descriptions, "#N" (Conway's rule) or "#R" with a rule
+// in S/B notation, and "#P x y" blocks of '.' and '*' rows.
+pub fn parse_105(s: &str) -> Result<RLE, ParseLifeError> {
+    let mut name = String::new();
+    let mut author = String::new();
+    let mut comments: Vec<String> = Vec::new();
+    let mut rule = Rule::default();
+    let mut cells: Vec<(i64, i64)> = Vec::new();
+    let (mut row, mut left) = (0, 0);
+
+    for (line_index, line) in s.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.trim_end();
+        if line_index == 0 && line.starts_with(LIFE_105_HEADER) {
+            continue;
+        }
+
+        if let Some(text) = line.strip_prefix("#D") {
+            let text = text.trim();
+            if let Some(value) = text.strip_prefix("Name:") {
+                name = value.trim().to_owned();
+            } else if let Some(value) = text.strip_prefix("Author:") {
+                author = value.trim().to_owned();
+            } else {
+                comments.push(text.to_owned());
+            }
+        } else if line.starts_with("#N") {
+            rule = Rule::default();
+        } else if let Some(text) = line.strip_prefix("#R") {
+            rule = match text.trim().parse::<Rule>() {
+                Ok(r) => r,
+                Err(_) => return Err(error(line_number, 3, text.trim()))
+            };
+        } else if let Some(text) = line.strip_prefix("#P") {
+            let (x, y) = parse_coordinates(text, line_number, 3)?;
+            row = y;
+            left = x;
+        } else if line.starts_with('#') {
+            continue;
+        } else {
+            for (col, c) in line.chars().enumerate() {
+                match c {
+                    '.' => {},
+                    '*' | 'O' => cells.push((row, left + col as i64)),
+                    _ => return Err(error(line_number, col + 1, &c.to_string()))
+                }
+            }
+            row += 1;
+        }
+    }
+
+    let mut rle = RLE::from_cells(&cells, &rule);
+    set_metadata(&mut rle, &name, &author, comments);
+    return Ok(rle)
+}
+
+pub fn write_105(rle: &RLE) -> String {
+    let mut result = format!("{}\n", LIFE_105_HEADER);
+    if !rle.name().is_empty() {
+        result.push_str(&format!("#D Name: {}\n", rle.name()));
+    }
+    if !rle.author().is_empty() {
+        result.push_str(&format!("#D Author: {}\n", rle.author()));
+    }
+    for comment in rle.comments() {
+        result.push_str(&format!("#D {}\n", comment));
+    }
+
+    match rle.rule() {
+        None => result.push_str("#N\n"),
+        Some(rule) if *rule == Rule::default() => result.push_str("#N\n"),
+        Some(Rule::Totalistic { become_alive, stay_alive }) => {
+            let digits = |counts: &Vec<usize>| counts.iter().map(|n| n.to_string()).collect::<String>();
+            result.push_str(&format!("#R {}/{}\n", digits(stay_alive), digits(become_alive)));
+        },
+        Some(rule) => result.push_str(&format!("#R {}\n", rule))
+    }
+
+    let (top, left) = rle.origin();
+    let mut blocks = vec![vec![Vec::new(); rle.height()]; rle.width().div_ceil(MAX_BLOCK_WIDTH)];
+    for (row, col) in rle.get_live_cells() {
+        let col = (col - left) as usize;
+        let line = &mut blocks[col / MAX_BLOCK_WIDTH][(row - top) as usize];
+        let col = col % MAX_BLOCK_WIDTH;
+        if line.len() <= col {
+            line.resize(col + 1, '.');
+        }
+        line[col] = '*';
+    }
+
+    for (index, rows) in blocks.into_iter().enumerate() {
+        // Empty rows above and below the cells of the block are left out
+        let first = match rows.iter().position(|row| !row.is_empty()) {
+            Some(first) => first,
+            None => continue
+        };
+        let last = rows.iter().rposition(|row| !row.is_empty()).unwrap_or(first);
+        result.push_str(&format!("#P {} {}\n", left + (index * MAX_BLOCK_WIDTH) as i64, top + first as i64));
+        for row in &rows[first..=last] {
+            if row.is_empty() {
+                result.push('.');
+            }
+            result.extend(row);
+            result.push('\n');
+        }
+    }
+    return result
+}
+
+// Life 1.06 is a list of "x y" pairs, one per live cell.
+pub fn parse_106(s: &str) -> Result<RLE, ParseLifeError> {
+    let mut cells: Vec<(i64, i64)> = Vec::new();
+    for (line_index, line) in s.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let (x, y) = parse_coordinates(line, line_index + 1, 1)?;
+        cells.push((y, x));
+    }
+    return Ok(RLE::from_cells(&cells, &Rule::default()))
+}
+
+pub fn write_106(rle: &RLE) -> String {
+    let mut result = format!("{}\n", LIFE_106_HEADER);
+    for (row, col) in rle.get_live_cells() {
+        result.push_str(&format!("{} {}\n", col, row));
+    }
+    return result
+}
+
+fn parse_coordinates(text: &str, line: usize, column: usize) -> Result<(i64, i64), ParseLifeError> {
+    let values: Vec<&str> = text.split_whitespace().collect();
+    if values.len() != 2 {
+        return Err(error(line, column, text.trim()))
+    }
+
+    let mut coordinates = [0i64; 2];
+    for (i, value) in values.iter().enumerate() {
+        coordinates[i] = match value.parse::<i64>() {
+            Ok(v) => v,
+            Err(_) => return Err(error(line, column + text.find(value).unwrap_or(0), value))
+        };
+    }
+    return Ok((coordinates[0], coordinates[1]))
+}
+
+fn set_metadata(rle: &mut RLE, name: &str, author: &str, comments: Vec<String>) {
+    rle.set_name(name);
+    rle.set_author(author);
+    for comment in comments {
+        rle.add_comment(&comment);
+    }
+}
+
+fn error(line: usize, column: usize, text: &str) -> ParseLifeError {
+    return ParseLifeError {
+        line,
+        column,
+        text: text.to_owned()
+    }
+}
+
+impl fmt::Display for ParseLifeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: unexpected '{}'", self.line, self.column, self.text)
+    }
+}
+
+impl std::error::Error for ParseLifeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::Grid;
+
+    const GLIDER_105: &str = "#Life 1.05\n#D Name: Glider\n#D Author: Richard K. Guy\n#D The smallest spaceship.\n#N\n#P -1 -1\n.*\n..*\n***\n";
+
+    fn rle_of(cells: &[(i64, i64)]) -> RLE {
+        let mut grid = Grid::new();
+        for (row, col) in cells {
+            grid.set_cell(*row, *col, true);
+        }
+        return RLE::from_grid(&grid)
+    }
+
+    #[test]
+    fn round_trips_life_105() {
+        let rle = parse_105(GLIDER_105).unwrap();
+        assert_eq!((rle.name(), rle.author()), ("Glider", "Richard K. Guy"));
+        assert_eq!(rle.comments(), ["The smallest spaceship."]);
+        assert_eq!(rle.rule(), Some(&Rule::default()));
+        assert_eq!(rle.get_live_cells(), vec![(-1, 0), (0, 1), (1, -1), (1, 0), (1, 1)]);
+        assert_eq!(write_105(&rle), GLIDER_105);
+    }
+
+    #[test]
+    fn reads_rules_in_survival_birth_order() {
+        let rle = parse_105("#Life 1.05\n#R 23/36\n#P 0 0\n***\n").unwrap();
+        assert_eq!(rle.rule(), Some(&"B36/S23".parse().unwrap()));
+        assert!(write_105(&rle).contains("\n#R 23/36\n"));
+        assert_eq!(parse_105(&write_105(&rle)).unwrap().rule(), rle.rule());
+
+        let error = parse_105("#Life 1.05\n#R 23/9\n").unwrap_err();
+        assert_eq!(error, ParseLifeError { line: 2, column: 3, text: "23/9".to_owned() });
+    }
+
+    #[test]
+    fn reads_several_blocks() {
+        let rle = parse_105("#Life 1.05\n#P 10 -3\n**\n.\n*\n#P -2 5\n.*\n").unwrap();
+        assert_eq!(rle.get_live_cells(), vec![(-3, 10), (-3, 11), (-1, 10), (5, -1)]);
+        let written = write_105(&rle);
+        assert_eq!(parse_105(&written).unwrap().get_live_cells(), rle.get_live_cells());
+    }
+
+    #[test]
+    fn splits_wide_patterns_into_blocks() {
+        let cells: Vec<(i64, i64)> = (0..200).map(|col| (col % 3, col - 50)).collect();
+        let rle = rle_of(&cells);
+        let written = write_105(&rle);
+        assert!(written.lines().all(|line| line.len() <= 80));
+        let blocks: Vec<&str> = written.lines().filter(|line| line.starts_with("#P")).collect();
+        assert_eq!(blocks, vec!["#P -50 0", "#P 30 0", "#P 110 0"]);
+        assert_eq!(parse_105(&written).unwrap().get_live_cells(), rle.get_live_cells());
+
+        // Blocks without live cells are left out and the others start at their first live row
+        let written = write_105(&rle_of(&[(0, 0), (4, 100)]));
+        assert_eq!(written, "#Life 1.05\n#N\n#P 0 0\n*\n#P 80 4\n....................*\n");
+    }
+
+    #[test]
+    fn reports_bad_life_105_lines() {
+        assert_eq!(parse_105("#Life 1.05\n#P 1\n").unwrap_err(), ParseLifeError { line: 2, column: 3, text: "1".to_owned() });
+        assert_eq!(parse_105("#P 0 y\n").unwrap_err(), ParseLifeError { line: 1, column: 6, text: "y".to_owned() });
+        let error = parse_105("#P 0 0\n.*x\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2, column 3: unexpected 'x'");
+    }
+
+    #[test]
+    fn round_trips_life_106() {
+        let text = "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n";
+        let rle = parse_106(text).unwrap();
+        assert_eq!(rle.get_live_cells(), vec![(-1, 0), (0, 1), (1, -1), (1, 0), (1, 1)]);
+        assert_eq!(write_106(&rle), text);
+        assert_eq!(parse_106(&write_106(&rle_of(&[(1000000000000, -7)]))).unwrap().get_live_cells(), vec![(1000000000000, -7)]);
+
+        // Comments and blank lines are skipped
+        assert_eq!(parse_106("#Life 1.06\n#D x\n\n 2  3 \n").unwrap().get_live_cells(), vec![(3, 2)]);
+        assert_eq!(parse_106("#Life 1.06\n1 2 3\n").unwrap_err(), ParseLifeError { line: 2, column: 1, text: "1 2 3".to_owned() });
+    }
+}
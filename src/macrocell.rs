@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::grid::{Grid, Rule};
+use crate::rle;
+
+// Golly's macrocell format: a quadtree written as a list of nodes, each
+// referring to earlier ones by 1-based line number (0 is an empty node).
+// Two-state files use 8x8 leaves written as rows of '.' and '*' ending in '$',
+// multi-state files use 2x2 leaves like "1 a b c d". The last node is the
+// root, centered on the origin.
+
+pub const MACROCELL_HEADER: &str = "[M2]";
+const LEAF_LEVEL: u32 = 3;
+const SMALL_LEAF_LEVEL: u32 = 1;
+const MAX_LEVEL: u32 = 62;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node {
+    // One bitmask per row, bit 0 is the leftmost cell
+    Leaf([u8; 8]),
+    // States of the nw, ne, sw and se cells
+    SmallLeaf([u8; 4]),
+    Branch { level: u32, children: [usize; 4] }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMacrocellError {
+    pub line: usize,
+    pub text: String,
+    pub reason: &'static str
+}
+
+#[derive(Debug, Clone)]
+pub struct Macrocell {
+    // None when the file doesn't name one, which means Conway's Life
+    rule: Option<Rule>,
+    comments: Vec<String>,
+    nodes: Vec<Node>
+}
+
+impl Macrocell {
+    // Cells are (row, col, state). Patterns with states above 1 are written
+    // with 2x2 leaves, others with 8x8 ones. Building the tree needs all cells
+    // at once, so this only suits patterns whose population fits in memory.
+    pub fn from_states(cells: &[(i64, i64, u8)], rule: &Rule) -> Macrocell {
+        let mut macrocell = Macrocell {
+            rule: Some(rule.clone()),
+            comments: Vec::new(),
+            nodes: Vec::new()
+        };
+
+        let cells: Vec<(i64, i64, u8)> = cells.iter().cloned().filter(|c| c.2 != 0).collect();
+        let leaf_level = if cells.iter().any(|c| c.2 > 1) { SMALL_LEAF_LEVEL } else { LEAF_LEVEL };
+        let mut level = leaf_level;
+        while level <= MAX_LEVEL && cells.iter().any(|(row, col, _)| !fits_in_level(*row, level) || !fits_in_level(*col, level)) {
+            level += 1;
+        }
+
+        let half = 1i64 << (level - 1);
+        let mut cache: HashMap<Node, usize> = HashMap::new();
+        let root = macrocell.build(cells, -half, -half, level, leaf_level, &mut cache);
+        if root == 0 {
+            // An empty root one level above the leaves, which are never written empty
+            macrocell.nodes.push(Node::Branch { level: level + 1, children: [0; 4] });
+        }
+        return macrocell
+    }
+
+    pub fn rule(&self) -> Option<&Rule> {
+        return self.rule.as_ref()
+    }
+
+    pub fn comments(&self) -> &[String] {
+        return &self.comments
+    }
+
+    pub fn add_comment(&mut self, comment: &str) {
+        self.comments.push(comment.to_owned());
+    }
+
+    pub fn level(&self) -> u32 {
+        match self.nodes.last() {
+            Some(node) => node_level(node),
+            None => 0
+        }
+    }
+
+    // Expands the shared subtrees into every single cell, memory grows with
+    // the population and not with the number of nodes. Patterns built for
+    // hashlife with billions of cells are out of reach, like they are for the
+    // grid itself.
+    pub fn get_live_cell_states(&self) -> Vec<(i64, i64, u8)> {
+        let mut cells = Vec::new();
+        self.for_each_cell(|row, col, state| cells.push((row, col, state)));
+        return cells
+    }
+
+    // Writes the cells straight into the grid, without collecting them first.
+    pub fn apply(&self, grid: &mut Grid) {
+        self.for_each_cell(|row, col, state| grid.set_state(row, col, state));
+        grid.set_rule(self.rule.clone().unwrap_or_default());
+    }
+
+    fn for_each_cell(&self, mut visit: impl FnMut(i64, i64, u8)) {
+        let level = self.level();
+        if level > 0 {
+            let half = 1i64 << (level - 1);
+            self.visit_cells(self.nodes.len(), -half, -half, &mut visit);
+        }
+    }
+
+    fn visit_cells(&self, index: usize, row: i64, col: i64, visit: &mut impl FnMut(i64, i64, u8)) {
+        if index == 0 {
+            return
+        }
+
+        match &self.nodes[index - 1] {
+            Node::Leaf(rows) => {
+                for (r, bits) in rows.iter().enumerate() {
+                    for c in 0..8 {
+                        if bits & (1 << c) != 0 {
+                            visit(row + r as i64, col + c as i64, 1);
+                        }
+                    }
+                }
+            },
+            Node::SmallLeaf(states) => {
+                for (i, state) in states.iter().enumerate() {
+                    if *state != 0 {
+                        visit(row + (i / 2) as i64, col + (i % 2) as i64, *state);
+                    }
+                }
+            },
+            Node::Branch { level, children } => {
+                let half = 1i64 << (level - 1);
+                self.visit_cells(children[0], row, col, visit);
+                self.visit_cells(children[1], row, col + half, visit);
+                self.visit_cells(children[2], row + half, col, visit);
+                self.visit_cells(children[3], row + half, col + half, visit);
+            }
+        }
+    }
+
+    fn build(&mut self, cells: Vec<(i64, i64, u8)>, row: i64, col: i64, level: u32, leaf_level: u32, cache: &mut HashMap<Node, usize>) -> usize {
+        if cells.is_empty() {
+            return 0
+        }
+
+        let node = if level == SMALL_LEAF_LEVEL {
+            let mut states = [0u8; 4];
+            for (r, c, state) in cells {
+                states[((r - row) * 2 + c - col) as usize] = state;
+            }
+            Node::SmallLeaf(states)
+        } else if level == leaf_level {
+            let mut rows = [0u8; 8];
+            for (r, c, _) in cells {
+                rows[(r - row) as usize] |= 1 << (c - col);
+            }
+            Node::Leaf(rows)
+        } else {
+            let half = 1i64 << (level - 1);
+            let mut quadrants: [Vec<(i64, i64, u8)>; 4] = Default::default();
+            for (r, c, state) in cells {
+                let quadrant = if r < row + half { 0 } else { 2 } + if c < col + half { 0 } else { 1 };
+                quadrants[quadrant].push((r, c, state));
+            }
+
+            let [nw, ne, sw, se] = quadrants;
+            let children = [
+                self.build(nw, row, col, level - 1, leaf_level, cache),
+                self.build(ne, row, col + half, level - 1, leaf_level, cache),
+                self.build(sw, row + half, col, level - 1, leaf_level, cache),
+                self.build(se, row + half, col + half, level - 1, leaf_level, cache)
+            ];
+            Node::Branch { level, children }
+        };
+
+        if let Some(index) = cache.get(&node) {
+            return *index
+        }
+        self.nodes.push(node.clone());
+        cache.insert(node, self.nodes.len());
+        return self.nodes.len()
+    }
+}
+
+impl FromStr for Macrocell {
+    type Err = ParseMacrocellError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut macrocell = Macrocell {
+            rule: None,
+            comments: Vec::new(),
+            nodes: Vec::new()
+        };
+        // Set by the first leaf or level 2 node, a file can't mix both kinds of leaves
+        let mut small_leaves: Option<bool> = None;
+
+        for (line_index, line) in s.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.trim();
+            if line_index == 0 {
+                if !line.starts_with(MACROCELL_HEADER) {
+                    return Err(error(line_number, line, "missing [M2] header"))
+                }
+                continue;
+            }
+
+            if let Some(rule) = line.strip_prefix("#R") {
+                // Multi-state files name rules like "WireWorld", those run as Conway's Life
+                match rule.trim().parse::<Rule>() {
+                    Ok(rule) => macrocell.rule = Some(rule),
+                    Err(_) if rle::is_named_rule(rule.trim()) => macrocell.rule = None,
+                    Err(_) => return Err(error(line_number, line, "invalid rule"))
+                }
+            } else if let Some(comment) = line.strip_prefix("#C") {
+                macrocell.comments.push(comment.trim().to_owned());
+            } else if line.starts_with('#') || line.is_empty() {
+                continue;
+            } else {
+                let node = if line.starts_with(|c: char| c.is_ascii_digit()) {
+                    parse_node(line, line_number, &macrocell.nodes)?
+                } else {
+                    parse_leaf(line, line_number)?
+                };
+                let is_small = match node {
+                    Node::Leaf(_) => Some(false),
+                    Node::SmallLeaf(_) | Node::Branch { level: 2, .. } => Some(true),
+                    _ => None
+                };
+                match (small_leaves, is_small) {
+                    (Some(true), Some(false)) => return Err(error(line_number, line, "8x8 leaf in a multi-state file")),
+                    (Some(false), Some(true)) => return Err(error(line_number, line, "level 1 or 2 node in a two-state file")),
+                    (None, _) => small_leaves = is_small,
+                    _ => {}
+                }
+                macrocell.nodes.push(node);
+            }
+        }
+
+        if macrocell.nodes.is_empty() {
+            return Err(error(s.lines().count(), "", "no nodes"))
+        }
+        Ok(macrocell)
+    }
+}
+
+impl fmt::Display for Macrocell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} (yagol {})", MACROCELL_HEADER, env!("CARGO_PKG_VERSION"))?;
+        if let Some(rule) = &self.rule {
+            writeln!(f, "#R {}", rule)?;
+        }
+        for comment in &self.comments {
+            writeln!(f, "#C {}", comment)?;
+        }
+
+        for node in &self.nodes {
+            match node {
+                Node::Leaf(rows) => {
+                    let used_rows = rows.iter().rposition(|r| *r != 0).map_or(0, |r| r + 1);
+                    for bits in &rows[..used_rows] {
+                        for c in 0..(8 - bits.leading_zeros()) {
+                            write!(f, "{}", if bits & (1 << c) != 0 { '*' } else { '.' })?;
+                        }
+                        write!(f, "$")?;
+                    }
+                    writeln!(f)?;
+                },
+                Node::SmallLeaf(states) => {
+                    writeln!(f, "1 {} {} {} {}", states[0], states[1], states[2], states[3])?;
+                },
+                Node::Branch { level, children } => {
+                    writeln!(f, "{} {} {} {} {}", level, children[0], children[1], children[2], children[3])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseMacrocellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} in '{}'", self.line, self.reason, self.text)
+    }
+}
+
+impl std::error::Error for ParseMacrocellError {}
+
+fn parse_leaf(line: &str, line_number: usize) -> Result<Node, ParseMacrocellError> {
+    let mut rows = [0u8; 8];
+    let (mut row, mut col) = (0, 0);
+    for c in line.chars() {
+        match c {
+            '$' => {
+                row += 1;
+                col = 0;
+                continue;
+            },
+            '.' => {},
+            '*' => {
+                if row >= 8 || col >= 8 {
+                    return Err(error(line_number, line, "leaf larger than 8x8"))
+                }
+                rows[row] |= 1 << col;
+            },
+            _ => return Err(error(line_number, line, "unexpected character"))
+        }
+        col += 1;
+    }
+    return Ok(Node::Leaf(rows))
+}
+
+fn parse_node(line: &str, line_number: usize, nodes: &[Node]) -> Result<Node, ParseMacrocellError> {
+    let values: Vec<u64> = match line.split_whitespace().map(|v| v.parse::<u64>()).collect() {
+        Ok(v) => v,
+        Err(_) => return Err(error(line_number, line, "expected numbers"))
+    };
+    if values.len() != 5 {
+        return Err(error(line_number, line, "expected a level and 4 children"))
+    }
+
+    if values[0] > MAX_LEVEL as u64 {
+        return Err(error(line_number, line, "level too large"))
+    }
+    let level = values[0] as u32;
+    if level == 0 {
+        return Err(error(line_number, line, "level 0 node"))
+    }
+    if level == 1 {
+        let mut states = [0u8; 4];
+        for (state, value) in states.iter_mut().zip(&values[1..]) {
+            if *value > 255 {
+                return Err(error(line_number, line, "state out of range"))
+            }
+            *state = *value as u8;
+        }
+        return Ok(Node::SmallLeaf(states))
+    }
+
+    let mut children = [0usize; 4];
+    for (child, value) in children.iter_mut().zip(&values[1..]) {
+        let index = *value as usize;
+        if index > nodes.len() {
+            return Err(error(line_number, line, "reference to a later node"))
+        }
+        if index != 0 && node_level(&nodes[index - 1]) + 1 != level {
+            return Err(error(line_number, line, "child has the wrong level"))
+        }
+        *child = index;
+    }
+    return Ok(Node::Branch { level, children })
+}
+
+fn node_level(node: &Node) -> u32 {
+    match node {
+        Node::Leaf(_) => LEAF_LEVEL,
+        Node::SmallLeaf(_) => SMALL_LEAF_LEVEL,
+        Node::Branch { level, .. } => *level
+    }
+}
+
+fn fits_in_level(coordinate: i64, level: u32) -> bool {
+    let half = 1i64 << (level - 1);
+    return coordinate >= -half && coordinate < half
+}
+
+fn error(line: usize, text: &str, reason: &'static str) -> ParseMacrocellError {
+    return ParseMacrocellError {
+        line,
+        text: text.to_owned(),
+        reason
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut cells: Vec<(i64, i64, u8)>) -> Vec<(i64, i64, u8)> {
+        cells.sort_unstable();
+        return cells
+    }
+
+    fn round_trip(cells: &[(i64, i64, u8)]) -> Macrocell {
+        let macrocell = Macrocell::from_states(cells, &Rule::default());
+        let reparsed: Macrocell = macrocell.to_string().parse().unwrap();
+        assert_eq!(sorted(reparsed.get_live_cell_states()), sorted(cells.to_vec()));
+        assert_eq!(reparsed.to_string(), macrocell.to_string());
+        return reparsed
+    }
+
+    fn parse_error(text: &str) -> ParseMacrocellError {
+        return text.parse::<Macrocell>().unwrap_err()
+    }
+
+    #[test]
+    fn reads_golly_files() {
+        let glider: Macrocell = "[M2] (golly 4.2)\n#R B3/S23\n.*$..*$***$\n4 0 0 0 1\n".parse().unwrap();
+        assert_eq!(glider.level(), 4);
+        assert_eq!(sorted(glider.get_live_cell_states()), vec![(0, 1, 1), (1, 2, 1), (2, 0, 1), (2, 1, 1), (2, 2, 1)]);
+
+        let wireworld: Macrocell = "[M2] (golly 4.2)\n#R WireWorld\n1 0 3 2 1\n1 3 0 0 0\n2 0 0 1 2\n".parse().unwrap();
+        assert_eq!(wireworld.rule(), None);
+        assert_eq!(sorted(wireworld.get_live_cell_states()), vec![(0, -1, 3), (0, 0, 3), (1, -2, 2), (1, -1, 1)]);
+    }
+
+    #[test]
+    fn round_trips_8x8_leaves() {
+        let glider = [(0, 1, 1), (1, 2, 1), (2, 0, 1), (2, 1, 1), (2, 2, 1)];
+        let macrocell = round_trip(&glider);
+        assert_eq!(macrocell.to_string().lines().last(), Some("$$$$.....*$......*$....***$"));
+
+        // Far apart cells share the empty nodes between them
+        let cells: Vec<(i64, i64, u8)> = vec![(-1000, -1000, 1), (-1000, 999, 1), (999, -1000, 1), (999, 999, 1)];
+        let macrocell = round_trip(&cells);
+        assert_eq!(macrocell.level(), 11);
+    }
+
+    #[test]
+    fn round_trips_multi_state_leaves() {
+        let cells = [(0, 0, 1), (0, 1, 2), (-1, -1, 3), (5, -7, 255)];
+        let macrocell = round_trip(&cells);
+        assert!(macrocell.to_string().lines().any(|line| line.starts_with("1 ")));
+    }
+
+    #[test]
+    fn round_trips_empty_patterns() {
+        let macrocell = round_trip(&[]);
+        assert!(macrocell.get_live_cell_states().is_empty());
+        let mut grid = Grid::new();
+        macrocell.apply(&mut grid);
+        assert_eq!(grid.get_population(), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let cases = [
+            ("#R B3/S23\n4 0 0 0 0", 1, "missing [M2] header"),
+            ("[M2]\n#R B3/S23", 2, "no nodes"),
+            ("[M2]\n#R B9/S23\n4 0 0 0 0", 2, "invalid rule"),
+            ("[M2]\n0 0 0 0 0", 2, "level 0 node"),
+            ("[M2]\n63 0 0 0 0", 2, "level too large"),
+            ("[M2]\n4294967300 0 0 0 0", 2, "level too large"),
+            ("[M2]\n4 0 0 0", 2, "expected a level and 4 children"),
+            ("[M2]\n4 0 x 0 0", 2, "expected numbers"),
+            ("[M2]\n1 0 256 0 0", 2, "state out of range"),
+            ("[M2]\n4 0 0 0 1", 2, "reference to a later node"),
+            ("[M2]\n*$\n5 0 0 0 1", 3, "child has the wrong level"),
+            ("[M2]\n*$\n2 0 0 0 0", 3, "level 1 or 2 node in a two-state file"),
+            ("[M2]\n1 0 0 0 1\n*$", 3, "8x8 leaf in a multi-state file"),
+            ("[M2]\n*o$", 2, "unexpected character"),
+            ("[M2]\n.........*$", 2, "leaf larger than 8x8")
+        ];
+        for (text, line, reason) in cases.iter() {
+            let error = parse_error(text);
+            assert_eq!((error.line, error.reason), (*line, *reason), "{}", text);
+        }
+    }
+}
@@ -2,7 +2,6 @@ extern crate glfw;
 
 extern crate gl;
 use std::io::Write;
-use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 use gl::types::*;
@@ -16,14 +15,31 @@ use std::{io, thread};
 use std::time::Instant;
 use glfw::{Action, Context, Key, MouseButton};
 use std::{ptr, sync::mpsc::Receiver, mem, str};
+use crossbeam_channel::Sender;
 use grid::Grid;
 
-use crate::rle::RLE;
-use crate::shader::Shader;
-
+use crate::cli::Command;
+use crate::graphics::shader::Shader;
+use crate::rle::{ApplyOptions, Placement};
+use crate::search::predecessor::Predecessor;
+
+mod apgcode;
+mod batch;
+mod cells;
+mod cli;
+mod convert;
+mod explore;
+mod format;
+mod graphics;
 mod grid;
+mod image;
+mod index;
+mod infer;
+mod library;
+mod life;
+mod macrocell;
 mod rle;
-mod shader;
+mod search;
 
 const VERTEX_SHADER_SOURCE: &str = r#"
     #version 330 core
@@ -33,9 +49,8 @@ const VERTEX_SHADER_SOURCE: &str = r#"
 
     out float fragmentActive;
 
-    const float stepSize = 0.02;
-
     void main() {
+        float stepSize = 2.0 / float(gridLength);
         int cell = gl_VertexID / 6;
         int row = cell / gridLength;
         int col = cell - (row * gridLength);
@@ -75,10 +90,7 @@ const FRAGMENT_SHADER_SOURCE: &str = r#"
        FragColor = fragmentActive == 1.0 ? vec4(1.0f, 0.5f, 0.2f, 1.0f) : vec4(0.2f, 0.3f, 0.3f, 1.0f);
     }
 "#;
-const GRID_LENGTH: usize = 100;
-
 const CELL_SIZE: usize = 2 * 3;
-const VERTEX_ARRAY_SIZE: usize = GRID_LENGTH * GRID_LENGTH * CELL_SIZE;
 
 struct InputStates {
     mouse_x: f64,
@@ -86,8 +98,133 @@ struct InputStates {
     mouse_left: bool
 }
 
+// Messages from the window to the simulation thread
+enum SimulationCommand {
+    TogglePause,
+    // Advance this many generations and pause
+    Step(usize),
+    // Go back to the loaded pattern
+    Reset,
+    // Put the --paste pattern onto the middle of the view
+    Paste
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match cli::parse(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    let options = match command {
+        Command::View(options) => options,
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return
+        },
+        Command::Convert(options) => {
+            if let Err(e) = convert::run(&options) {
+                eprintln!("convert: {}", e);
+                std::process::exit(1);
+            }
+            return
+        },
+        Command::Run(options) => {
+            match batch::run_command(&options) {
+                Ok(result) => if let Some((condition, period)) = result.stopped {
+                    eprintln!("stopped at generation {}: {} (period {})", result.generation, condition, period);
+                },
+                Err(e) => {
+                    eprintln!("run: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return
+        },
+        Command::Infer(options) => {
+            match infer::run_command(&options) {
+                Ok(rule) => print!("{}", rule),
+                Err(e) => {
+                    eprintln!("infer: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return
+        },
+        Command::Predecessor(options) => {
+            match search::predecessor::run_command(&options) {
+                Ok(Predecessor::Found(parent)) => print!("{}", parent),
+                Ok(Predecessor::NoneWithinBounds) => {
+                    eprintln!("predecessor: none within {} cells of the pattern", options.margin);
+                    std::process::exit(1);
+                },
+                Err(e) => {
+                    eprintln!("predecessor: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            return
+        },
+        Command::Explore(options) => {
+            if let Err(e) = explore::run_command(&options) {
+                eprintln!("explore: {}", e);
+                std::process::exit(1);
+            }
+            return
+        },
+        Command::Search(options) => {
+            if let Err(e) = index::run_command(&options) {
+                eprintln!("search: {}", e);
+                std::process::exit(1);
+            }
+            return
+        },
+        Command::Video(options) => {
+            if let Err(e) = image::y4m::run_command(&options) {
+                eprintln!("video: {}", e);
+                std::process::exit(1);
+            }
+            return
+        },
+        Command::List(options) => {
+            if let Err(e) = library::run_command(&options) {
+                eprintln!("list: {}", e);
+                std::process::exit(1);
+            }
+            return
+        }
+    };
+
     let mut grid = Grid::new();
+    let pattern = match &options.index {
+        Some(dir) => cli::load_indexed_into(dir, &options.pattern, &mut grid),
+        None => cli::load_into(&options.pattern, &mut grid)
+    };
+    let pattern = match pattern {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(rule) = options.rule.clone() {
+        grid.set_rule(rule);
+    }
+    let paste = match &options.paste {
+        Some(paste) => match cli::load_pattern(&paste.pattern) {
+            Ok(rle) => Some((rle, paste.options)),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None
+    };
+
+    let grid_length = options.zoom;
+    let vertex_array_size = grid_length * grid_length * CELL_SIZE;
     
     let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
     glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
@@ -95,7 +232,7 @@ fn main() {
     #[cfg(target_os = "macos")]
     glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
     
-    let (mut window, events) = glfw.create_window(800, 800, "yagol", glfw::WindowMode::Windowed)
+    let (mut window, events) = glfw.create_window(options.window_size.0, options.window_size.1, "yagol", glfw::WindowMode::Windowed)
     .expect("Failed to create GLFW window.");
     
     window.make_current();
@@ -107,12 +244,12 @@ fn main() {
     gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
     
     
-    let vtx_arr_primary: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(vec![0.0; VERTEX_ARRAY_SIZE]));
-    let vtx_arr_secondary: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(vec![0.0; VERTEX_ARRAY_SIZE]));
+    let vtx_arr_primary: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(vec![0.0; vertex_array_size]));
+    let vtx_arr_secondary: Arc<Mutex<Vec<f32>>> = Arc::new(Mutex::new(vec![0.0; vertex_array_size]));
     let buffer_order = Arc::new(Mutex::new(false));
 
     let shader = Shader::new(VERTEX_SHADER_SOURCE, FRAGMENT_SHADER_SOURCE);
-    shader.set_int("gridLength", GRID_LENGTH as i32);
+    shader.set_int("gridLength", grid_length as i32);
     let vao = setup_vertex_buffer();
     
     let mut button_states = InputStates {mouse_x: 0.0, mouse_y: 0.0, mouse_left: false};
@@ -121,31 +258,46 @@ fn main() {
     let mut mouse_last_left = false;
     
     
-    let view_x: Arc<Mutex<i64>> = Arc::new(Mutex::new(0));
-    let view_y: Arc<Mutex<i64>> = Arc::new(Mutex::new(0));
+    // Start with the pattern in the middle of the view
+    let (top, left) = pattern.origin();
+    let view_x: Arc<Mutex<i64>> = Arc::new(Mutex::new(left + (pattern.width() as i64 - grid_length as i64) / 2));
+    let view_y: Arc<Mutex<i64>> = Arc::new(Mutex::new(top + (pattern.height() as i64 - grid_length as i64) / 2));
     
     
     let (tx, rx) = crossbeam_channel::bounded(1);
+    let (command_tx, command_rx) = crossbeam_channel::unbounded();
     let view_x_clone = Arc::clone(&view_x);
     let view_y_clone = Arc::clone(&view_y);
 
     let arr_primary = vtx_arr_primary.clone();
     let arr_secondary = vtx_arr_secondary.clone();
     let buffer_order_clone = buffer_order.clone();
+    let delay_time = options.delay.as_micros();
+    let step = options.step;
+    let mut paused = options.paused;
+    let initial_grid = grid.clone();
 
     thread::spawn(move || {
-        let s = r#"
-
-"#;
-        let rle = RLE::from_str(s).unwrap();
-        grid.set_cell(50, 50, true);
-        grid.set_cell(49, 50, true);
-        grid.set_cell(49, 49, true);
-        grid.set_cell(48, 50, true);
-        grid.set_cell(50, 51, true);
-        //rle.apply(&mut grid);
         loop {
             let now = Instant::now();
+            for command in command_rx.try_iter() {
+                match command {
+                    SimulationCommand::TogglePause => paused = !paused,
+                    SimulationCommand::Step(generations) => {
+                        paused = true;
+                        for _ in 0..generations {
+                            grid.calc_next_generation();
+                        }
+                    },
+                    SimulationCommand::Reset => grid = initial_grid.clone(),
+                    SimulationCommand::Paste => if let Some((rle, paste_options)) = &paste {
+                        let row = *view_y_clone.lock().unwrap() + grid_length as i64 / 2;
+                        let col = *view_x_clone.lock().unwrap() + grid_length as i64 / 2;
+                        rle.apply_with(&mut grid, &ApplyOptions { placement: Placement::CenteredOn(row, col), ..*paste_options });
+                    }
+                }
+            }
+
             let x = view_x_clone.lock().unwrap().clone();
             let y = view_y_clone.lock().unwrap().clone();
 
@@ -157,20 +309,22 @@ fn main() {
 
             if buf_order {
                 if let Ok(mut lock) = arr_secondary.lock() {
-                    grid.get_grid(y, x, GRID_LENGTH, &mut *lock);
+                    grid.get_grid(y, x, grid_length, &mut *lock);
                 }
             }
             else {
                 if let Ok(mut lock) = arr_primary.lock() {
-                    grid.get_grid(y, x, GRID_LENGTH, &mut *lock);
+                    grid.get_grid(y, x, grid_length, &mut *lock);
                 }
             }
             
 
-            grid.calc_next_generation();
+            // Keep rendering while paused so the view can still be moved
+            if !paused {
+                grid.calc_next_generation();
+            }
 
             let elapsed = now.elapsed().as_micros();
-            let delay_time = 50000;
             let sleep_time = if elapsed <= delay_time {(delay_time - elapsed) as u64} else { 0};
 
             tx.send("New Generation").unwrap();
@@ -180,7 +334,7 @@ fn main() {
     });
 
 
-    update_vertex_buffer(vao, &vec![0.0; VERTEX_ARRAY_SIZE]);
+    update_vertex_buffer(vao, &vec![0.0; vertex_array_size]);
 
     while !window.should_close() {
         let now = Instant::now();
@@ -202,7 +356,7 @@ fn main() {
             }
         }
 
-        process_events(&mut window, &events, &mut button_states);
+        process_events(&mut window, &events, &mut button_states, &command_tx, step);
 
 
         if button_states.mouse_left {
@@ -228,7 +382,7 @@ fn main() {
 
             shader.use_shader();
             gl::BindVertexArray(vao);
-            gl::DrawArrays(gl::TRIANGLES, 0, VERTEX_ARRAY_SIZE as i32);
+            gl::DrawArrays(gl::TRIANGLES, 0, vertex_array_size as i32);
             gl::BindVertexArray(0);
         }
         
@@ -240,7 +394,7 @@ fn main() {
     }
 }
 
-fn process_events(window: &mut glfw::Window, events: &Receiver<(f64, glfw::WindowEvent)>, input_states: &mut InputStates) {
+fn process_events(window: &mut glfw::Window, events: &Receiver<(f64, glfw::WindowEvent)>, input_states: &mut InputStates, commands: &Sender<SimulationCommand>, step: usize) {
     for (_, event) in glfw::flush_messages(events) {
         match event {
             glfw::WindowEvent::FramebufferSize(width, height) => {
@@ -262,6 +416,11 @@ fn process_events(window: &mut glfw::Window, events: &Receiver<(f64, glfw::Windo
                 input_states.mouse_y = height;
             },
             glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => window.set_should_close(true),
+            glfw::WindowEvent::Key(Key::Space, _, Action::Press, _) => commands.send(SimulationCommand::TogglePause).unwrap(),
+            glfw::WindowEvent::Key(Key::S, _, Action::Press, _) => commands.send(SimulationCommand::Step(1)).unwrap(),
+            glfw::WindowEvent::Key(Key::N, _, Action::Press, _) => commands.send(SimulationCommand::Step(step)).unwrap(),
+            glfw::WindowEvent::Key(Key::R, _, Action::Press, _) => commands.send(SimulationCommand::Reset).unwrap(),
+            glfw::WindowEvent::Key(Key::P, _, Action::Press, _) => commands.send(SimulationCommand::Paste).unwrap(),
             _ => {}
         }
     }
@@ -1,93 +1,311 @@
-use crate::grid::Rule;
+use crate::grid::{ParseRuleError, Rule};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
 use std::str::FromStr;
 use regex::{Regex};
 
-use crate::grid::Grid;
+use crate::grid::{Grid, Region};
 
 extern crate regex;
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Tag {
     DeadCell,
     AliveCell,
+    // States above 1, only found in multi-state patterns
+    State(u8),
     EoL,
     EoF
 }
 
 type Pattern = (Tag, usize);
+
+// Where RLE::apply_with puts the pattern: at the origin stored in the file,
+// with the top-left corner at (row, col) or centered on (row, col).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Placement {
+    Origin,
+    At(i64, i64),
+    CenteredOn(i64, i64)
+}
+
+// Rotations are clockwise, flips mirror the pattern along the named axis.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    FlipDiagonal,
+    FlipAntiDiagonal
+}
+
+// Copy replaces everything inside the pattern's box, Or only adds live cells,
+// And keeps the grid's cells where the pattern is alive and Xor toggles them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PasteMode {
+    Copy,
+    Or,
+    And,
+    Xor
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ApplyOptions {
+    pub placement: Placement,
+    pub transform: Transform,
+    pub mode: PasteMode,
+    pub adopt_rule: bool
+}
+#[derive(Debug, Clone)]
 pub struct RLE {
     width: usize,
     height: usize,
     name: String,
     author: String,
+    comments: Vec<String>,
+    // Top-left corner as (row, col)
     origin: (i64, i64),
-    rule: String,
+    // None when the file doesn't name one, which means Conway's Life
+    rule: Option<Rule>,
+    // Rule as written in the file when it isn't life-like, like "WireWorld"
+    rule_name: Option<String>,
     patterns: Vec<Pattern>
 }
 
-const HEADER_REGEX_STRING: &str = r"^x\s?=\s?(\d+),\s?y\s?=\s?(\d+),\s?rule\s?=\s?(.+)$";
-const PATTERN_REGEX_STRING: &str = r"(\d*)([bo$])";
-const RULE_REGEX_STRING: &str = r"[Bb]?(\d+)\s*/\s*[Ss]?(\d+)";
-
+const MAX_LINE_LENGTH: usize = 70;
+const PROGRESS_INTERVAL: u64 = 1 << 20;
+const HEADER_REGEX_STRING: &str = r"^\s*x\s*=\s*([^,\s]*)\s*,\s*y\s*=\s*([^,\s]*)\s*(?:,\s*rule\s*=\s*(.*?))?\s*$";
 
+// Lines and columns are 1-based, text is the offending part of the input.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ParseRleError {}
+pub enum ParseRleError {
+    BadHeader { line: usize, column: usize, text: String },
+    BadRule { line: usize, column: usize, text: String, error: ParseRuleError },
+    BadRunCount { line: usize, column: usize, text: String },
+    UnsupportedState { line: usize, column: usize, text: String },
+    SizeMismatch { line: usize, column: usize, text: String, width: usize, height: usize },
+    // The input ended without the closing '!', the position is right after the last character
+    MissingEnd { line: usize, column: usize }
+}
 
 impl RLE {
-    pub fn apply(&self, grid: &mut Grid) {
-        self.set_grid(grid);
-        self.set_rule(grid);
+    pub fn from_cells(cells: &[(i64, i64)], rule: &Rule) -> RLE {
+        let cells: Vec<(i64, i64, u8)> = cells.iter().map(|(row, col)| (*row, *col, 1)).collect();
+        return RLE::from_states(&cells, rule)
     }
 
-    fn set_grid(&self, grid: &mut Grid) {
-        let mut row: i64 = 0;
-        let mut col: i64 = 0;
-        for (tag, count) in self.patterns.iter() {
-            for _ in 0..*count {
-                match tag {
-                    Tag::DeadCell => grid.set_cell(row, col, false),
-                    Tag::AliveCell => grid.set_cell(row, col, true),
-                    Tag::EoL => {
-                        row += 1;
-                        col = 0;
-                        continue;
-                    },
-                    Tag::EoF => return,
-                }
+    // Cells are (row, col, state), the pattern covers their bounding box.
+    pub fn from_states(cells: &[(i64, i64, u8)], rule: &Rule) -> RLE {
+        let min_row = cells.iter().map(|c| c.0).min().unwrap_or(0);
+        let max_row = cells.iter().map(|c| c.0).max().unwrap_or(-1);
+        let min_col = cells.iter().map(|c| c.1).min().unwrap_or(0);
+        let max_col = cells.iter().map(|c| c.1).max().unwrap_or(-1);
+        return RLE::from_states_in(cells, (min_row, min_col, max_row, max_col), rule)
+    }
+
+    pub fn from_grid(grid: &Grid) -> RLE {
+        let cells = grid.get_live_cell_states();
+        let bounds = grid.get_bounding_box().unwrap_or((0, 0, -1, -1));
+        return RLE::from_states_in(&cells, bounds, grid.get_rule())
+    }
+
+    pub fn from_region(grid: &Grid, region: Region) -> RLE {
+        let (min_row, min_col, max_row, max_col) = region;
+        let cells: Vec<(i64, i64, u8)> = grid.get_live_cell_states().into_iter()
+            .filter(|(r, c, _)| *r >= min_row && *r <= max_row && *c >= min_col && *c <= max_col)
+            .collect();
+        return RLE::from_states_in(&cells, region, grid.get_rule())
+    }
 
-                col += 1;
+    pub fn from_cells_in(cells: &[(i64, i64)], bounds: Region, rule: &Rule) -> RLE {
+        let cells: Vec<(i64, i64, u8)> = cells.iter().map(|(row, col)| (*row, *col, 1)).collect();
+        return RLE::from_states_in(&cells, bounds, rule)
+    }
+
+    // Cells are (row, col, state), cells in state 0 are skipped.
+    pub fn from_states_in(cells: &[(i64, i64, u8)], bounds: Region, rule: &Rule) -> RLE {
+        let (min_row, min_col, max_row, max_col) = bounds;
+        let mut cells: Vec<(i64, i64, u8)> = cells.iter().cloned().filter(|c| c.2 != 0).collect();
+        cells.sort_unstable();
+        cells.dedup_by_key(|(row, col, _)| (*row, *col));
+
+        let mut patterns: Vec<Pattern> = Vec::new();
+        let (mut row, mut col) = (min_row, min_col);
+        for (r, c, state) in cells {
+            if r > row {
+                push_pattern(&mut patterns, Tag::EoL, (r - row) as usize);
+                row = r;
+                col = min_col;
+            }
+            if c > col {
+                push_pattern(&mut patterns, Tag::DeadCell, (c - col) as usize);
             }
+            push_pattern(&mut patterns, state_tag(state), 1);
+            col = c + 1;
+        }
+
+        RLE {
+            width: (max_col - min_col + 1).max(0) as usize,
+            height: (max_row - min_row + 1).max(0) as usize,
+            name: String::new(),
+            author: String::new(),
+            comments: Vec::new(),
+            origin: (min_row, min_col),
+            rule: Some(rule.clone()),
+            rule_name: None,
+            patterns
         }
     }
 
-    fn set_rule(&self, grid: &mut Grid) {
-        let rule_regex = Regex::new(RULE_REGEX_STRING).unwrap();
-        if !rule_regex.is_match(self.rule.as_str()) {
-            return
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    pub fn set_author(&mut self, author: &str) {
+        self.author = author.to_owned();
+    }
+
+    // Moves the pattern so its top-left corner is at origin (row, col).
+    pub fn set_origin(&mut self, origin: (i64, i64)) {
+        self.origin = origin;
+    }
+
+    pub fn add_comment(&mut self, comment: &str) {
+        self.comments.push(comment.to_owned());
+    }
+
+    pub fn width(&self) -> usize {
+        return self.width
+    }
+
+    pub fn height(&self) -> usize {
+        return self.height
+    }
+
+    pub fn name(&self) -> &str {
+        return &self.name
+    }
+
+    pub fn author(&self) -> &str {
+        return &self.author
+    }
+
+    pub fn comments(&self) -> &[String] {
+        return &self.comments
+    }
+
+    pub fn origin(&self) -> (i64, i64) {
+        return self.origin
+    }
+
+    pub fn rule(&self) -> Option<&Rule> {
+        return self.rule.as_ref()
+    }
+
+    // Rules that aren't life-like are kept as text and run as Conway's Life.
+    pub fn rule_name(&self) -> Option<&str> {
+        return self.rule_name.as_deref()
+    }
+
+    pub fn get_live_cells(&self) -> Vec<(i64, i64)> {
+        return self.get_live_cell_states().into_iter().map(|(row, col, _)| (row, col)).collect()
+    }
+
+    pub fn get_live_cell_states(&self) -> Vec<(i64, i64, u8)> {
+        let mut cells = Vec::new();
+        let (mut row, mut col) = self.origin;
+        for (tag, count) in self.patterns.iter() {
+            match tag {
+                Tag::DeadCell => col += *count as i64,
+                Tag::AliveCell | Tag::State(_) => {
+                    for _ in 0..*count {
+                        cells.push((row, col, tag_state(*tag)));
+                        col += 1;
+                    }
+                },
+                Tag::EoL => {
+                    row += *count as i64;
+                    col = self.origin.1;
+                },
+                Tag::EoF => break
+            }
         }
-        let cap = rule_regex.captures(self.rule.as_str()).unwrap();
+        return cells
+    }
 
-        let mut become_alive: Vec<usize> = Vec::new();
-        let mut stay_alive: Vec<usize> = Vec::new();
+    pub fn apply(&self, grid: &mut Grid) {
+        self.apply_with(grid, &ApplyOptions::default());
+    }
 
-        let become_values = &cap[1];
-        let stay_values = &cap[2];
+    pub fn apply_with(&self, grid: &mut Grid, options: &ApplyOptions) {
+        let (height, width) = options.transform.size(self.height, self.width);
+        let (top, left) = match options.placement {
+            Placement::Origin => self.origin,
+            Placement::At(row, col) => (row, col),
+            Placement::CenteredOn(row, col) => (row - height as i64 / 2, col - width as i64 / 2)
+        };
 
-        for c in become_values.chars() {
-            let num = c.to_digit(10).unwrap() as usize;
-            become_alive.push(num);
+        let mut cells: HashMap<(i64, i64), u8> = HashMap::new();
+        for (row, col, state) in self.get_live_cell_states() {
+            let (row, col) = options.transform.apply(row - self.origin.0, col - self.origin.1, self.height, self.width);
+            cells.insert((top + row, left + col), state);
         }
 
-        for c in stay_values.chars() {
-            let num = c.to_digit(10).unwrap() as usize;
-            stay_alive.push(num);
+        // Copy and And also affect the cells of the grid inside the pattern's box
+        if options.mode == PasteMode::Copy || options.mode == PasteMode::And {
+            let (bottom, right) = (top + height as i64, left + width as i64);
+            let covered: Vec<(i64, i64)> = grid.get_live_cells().into_iter()
+                .filter(|(r, c)| *r >= top && *r < bottom && *c >= left && *c < right)
+                .collect();
+            for (row, col) in covered {
+                if !cells.contains_key(&(row, col)) {
+                    grid.set_state(row, col, 0);
+                }
+            }
         }
 
-        grid.set_rule(Rule {
-            become_alive,
-            stay_alive
-        });
-        
+        for ((row, col), state) in cells {
+            let current = grid.get_state(row, col);
+            let next = match options.mode {
+                PasteMode::Copy | PasteMode::Or => state,
+                PasteMode::And => current,
+                PasteMode::Xor => if current == 0 { state } else { 0 }
+            };
+            grid.set_state(row, col, next);
+        }
+
+        if options.adopt_rule {
+            self.set_rule(grid);
+        }
+    }
+
+    // Joins neighboring runs of the same tag and drops dead cells at the end of a row.
+    fn merged_patterns(&self) -> Vec<Pattern> {
+        let mut merged: Vec<Pattern> = Vec::new();
+        for (tag, count) in &self.patterns {
+            if *tag == Tag::EoF {
+                break;
+            }
+            if *tag == Tag::EoL {
+                if let Some((Tag::DeadCell, _)) = merged.last() {
+                    merged.pop();
+                }
+            }
+            push_pattern(&mut merged, *tag, *count);
+        }
+        while let Some((Tag::DeadCell, _)) | Some((Tag::EoL, _)) = merged.last() {
+            merged.pop();
+        }
+        return merged
+    }
+
+    // A pattern without a rule runs under Conway's Life.
+    fn set_rule(&self, grid: &mut Grid) {
+        grid.set_rule(self.rule.clone().unwrap_or_default());
     }
 }
 
@@ -97,82 +315,878 @@ impl FromStr for RLE {
 
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let header_regex = Regex::new(HEADER_REGEX_STRING).unwrap();
-        let pattern_regex = Regex::new(PATTERN_REGEX_STRING).unwrap();
+        let mut parser = Parser::new();
+        let mut patterns: Vec<Pattern> = Vec::new();
+        for line in s.lines() {
+            if parser.done {
+                break;
+            }
+            parser.parse_line(line, |tag, run| patterns.push((tag, run)))?;
+        }
+
+        let mut rle = parser.finish()?;
+        rle.patterns = patterns;
+        Ok(rle)
+    }
+}
 
-        let mut height = 0;
-        let mut width = 0;
-        let mut origin = (0, 0);
-        let mut rule = String::new();
-        let mut name = String::new();
-        let mut author = String::new();
+#[derive(Debug)]
+pub enum ReadRleError {
+    Io(io::Error),
+    Parse(ParseRleError)
+}
 
-        let mut pattern_lines = String::new();
-        for line in s.lines() {
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ReadProgress {
+    pub bytes: u64,
+    pub lines: usize,
+    pub live_cells: u64
+}
+
+// Decodes the pattern line by line straight into the grid instead of building
+// the run list first, so huge files only cost the memory of the grid. Only
+// live cells are written and the rule is adopted like RLE::apply does.
+// progress is called about every megabyte and once at the end; the returned
+// RLE holds the metadata without any cells.
+pub fn read_into<R: io::Read>(reader: R, grid: &mut Grid, mut progress: impl FnMut(ReadProgress)) -> Result<RLE, ReadRleError> {
+    let mut reader = io::BufReader::new(reader);
+    let mut parser = Parser::new();
+    let mut state = ReadProgress::default();
+    let mut next_report = PROGRESS_INTERVAL;
+    let mut line = String::new();
+    let (mut row, mut col) = (0i64, 0i64);
+
+    while !parser.done {
+        line.clear();
+        let bytes = reader.read_line(&mut line)?;
+        if bytes == 0 {
+            break;
+        }
+        state.bytes += bytes as u64;
+        state.lines += 1;
+
+        let (top, left) = parser.rle.origin;
+        let mut live_cells = 0;
+        parser.parse_line(&line, |tag, run| match tag {
+            Tag::DeadCell => col += run as i64,
+            Tag::AliveCell | Tag::State(_) => {
+                for _ in 0..run {
+                    grid.set_state(top + row, left + col, tag_state(tag));
+                    col += 1;
+                }
+                live_cells += run as u64;
+            },
+            Tag::EoL => {
+                row += run as i64;
+                col = 0;
+            },
+            Tag::EoF => {}
+        })?;
+        state.live_cells += live_cells;
+
+        if state.bytes >= next_report {
+            progress(state);
+            next_report = state.bytes + PROGRESS_INTERVAL;
+        }
+    }
+
+    let rle = parser.finish()?;
+    rle.set_rule(grid);
+    progress(state);
+    return Ok(rle)
+}
+
+// Line based RLE tokenizer shared by from_str and read_into. Metadata ends
+// up in rle, runs are handed to a callback as they are decoded.
+struct Parser {
+    rle: RLE,
+    has_header: bool,
+    // Run count being read, with the column it started at
+    count: Option<(String, usize)>,
+    // Pending 'p' to 'y' prefix of a multi-state cell, with its column
+    prefix: Option<(char, usize)>,
+    row: usize,
+    col: usize,
+    line_number: usize,
+    // Line and column right after the last character that wasn't whitespace
+    end: (usize, usize),
+    done: bool
+}
+
+impl Parser {
+    fn new() -> Parser {
+        return Parser {
+            rle: RLE {
+                width: 0,
+                height: 0,
+                name: String::new(),
+                author: String::new(),
+                comments: Vec::new(),
+                origin: (0, 0),
+                rule: None,
+                rule_name: None,
+                patterns: Vec::new()
+            },
+            has_header: false,
+            count: None,
+            prefix: None,
+            row: 0,
+            col: 0,
+            line_number: 0,
+            end: (1, 1),
+            done: false
+        }
+    }
+
+    fn parse_line(&mut self, line: &str, mut emit: impl FnMut(Tag, usize)) -> Result<(), ParseRleError> {
+        self.line_number += 1;
+        let line_number = self.line_number;
+        let trimmed = line.trim_end();
+        if !trimmed.is_empty() {
+            self.end = (line_number, trimmed.chars().count() + 1);
+        }
+        if !self.has_header {
             if line.starts_with("#") {
-                let (nameval, authorval, originval) = parse_comment(line);
-                name = nameval;
-                author = authorval;
-                origin = originval;
-                continue;
+                return parse_comment(line, line_number, &mut self.rle)
+            }
+            if line.trim().is_empty() {
+                return Ok(())
             }
-            if header_regex.is_match(line) {
-                let (widthval, heightval, ruleval) = parse_header(line);
-                rule = ruleval;
-                height = heightval;
-                width = widthval;
 
-                continue;
+            let (width, height, rule) = parse_header(line.trim_end(), line_number)?;
+            self.rle.width = width;
+            self.rle.height = height;
+            if let Some((text, column)) = rule {
+                set_rule(&mut self.rle, text, line_number, column)?;
             }
-            
-            pattern_lines.push_str(line);
+            self.has_header = true;
+            return Ok(())
         }
-        
-        let mut patterns: Vec<Pattern> = Vec::new();
-        for cap in pattern_regex.captures_iter(&pattern_lines) {
-            let count = cap.get(1).map_or(1, |c| {
-                match c.as_str().parse::<usize>() {
-                    Ok(v) => v,
-                    Err(_) => 1,
-                }
-            });
 
-            let tag: Tag = cap.get(2).map_or(Tag::EoF, |c| {
-                match c.as_str() {
-                    "b" => Tag::DeadCell,
-                    "o" => Tag::AliveCell,
-                    "$" => Tag::EoL,
-                    _ => Tag::EoF
+        for (char_index, c) in line.chars().enumerate() {
+            let column = char_index + 1;
+            if let Some((prefix, prefix_column)) = self.prefix {
+                if !('A'..='X').contains(&c) || state_number(Some(prefix), c).is_none() {
+                    let text = format!("{}{}", prefix, c);
+                    return Err(ParseRleError::UnsupportedState { line: line_number, column: prefix_column, text })
                 }
-            });
+            } else if c.is_ascii_digit() {
+                match self.count.as_mut() {
+                    Some((digits, _)) => digits.push(c),
+                    None => self.count = Some((c.to_string(), column))
+                }
+                continue;
+            } else if c.is_whitespace() {
+                continue;
+            } else if c == '!' {
+                self.done = true;
+                return Ok(())
+            } else if ('p'..='y').contains(&c) {
+                self.prefix = Some((c, column));
+                continue;
+            }
+
+            let prefix = self.prefix.take();
+            let symbol = match prefix {
+                Some((p, _)) => format!("{}{}", p, c),
+                None => c.to_string()
+            };
+            let (run, run_text, run_column) = match self.count.take() {
+                Some((digits, start)) => match digits.parse::<usize>() {
+                    Ok(n) if n > 0 => (n, format!("{}{}", digits, symbol), start),
+                    _ => return Err(ParseRleError::BadRunCount { line: line_number, column: start, text: digits })
+                },
+                None => (1, symbol, prefix.map_or(column, |p| p.1))
+            };
+
+            let tag = match c {
+                'b' | '.' => Tag::DeadCell,
+                'o' => Tag::AliveCell,
+                '$' => Tag::EoL,
+                'A'..='X' => state_tag(state_number(prefix.map(|p| p.0), c).unwrap()),
+                _ => return Err(ParseRleError::UnsupportedState { line: line_number, column, text: c.to_string() })
+            };
 
-            patterns.push((tag, count));
+            if tag == Tag::EoL {
+                self.row += run;
+                self.col = 0;
+            } else {
+                self.col += run;
+            }
+            if tag != Tag::EoL && (self.col > self.rle.width || self.row >= self.rle.height) {
+                return Err(ParseRleError::SizeMismatch {
+                    line: line_number,
+                    column: run_column,
+                    text: run_text,
+                    width: self.rle.width,
+                    height: self.rle.height
+                })
+            }
+
+            emit(tag, run);
         }
+        Ok(())
+    }
 
-        Ok(RLE {
-            author,
-            name,
-            origin,
-            rule,
-            height,
-            width,
-            patterns
-        })
+    fn finish(self) -> Result<RLE, ParseRleError> {
+        if let (false, Some((prefix, column))) = (self.done, self.prefix) {
+            return Err(ParseRleError::UnsupportedState { line: self.line_number, column, text: prefix.to_string() })
+        }
+        if let (false, Some((digits, column))) = (self.done, self.count) {
+            return Err(ParseRleError::BadRunCount { line: self.line_number, column, text: digits })
+        }
+        if !self.done {
+            let (line, column) = self.end;
+            return Err(ParseRleError::MissingEnd { line, column })
+        }
+        Ok(self.rle)
     }
 }
 
-fn parse_comment(s: &str) -> (String, String, (i64, i64)) {
-    return (String::from(""), String::from(""), (0, 0))
+impl Default for ApplyOptions {
+    fn default() -> ApplyOptions {
+        ApplyOptions {
+            placement: Placement::Origin,
+            transform: Transform::Identity,
+            mode: PasteMode::Copy,
+            adopt_rule: true
+        }
+    }
 }
 
-fn parse_header(s: &str) -> (usize, usize, String) {
+impl Transform {
+    // Size of a height x width box after the transformation, as (height, width).
+    pub fn size(&self, height: usize, width: usize) -> (usize, usize) {
+        match self {
+            Transform::Identity | Transform::Rotate180 | Transform::FlipHorizontal | Transform::FlipVertical => (height, width),
+            _ => (width, height)
+        }
+    }
+
+    // Moves (row, col) inside a height x width box to its place in the transformed box.
+    pub fn apply(&self, row: i64, col: i64, height: usize, width: usize) -> (i64, i64) {
+        let (last_row, last_col) = (height as i64 - 1, width as i64 - 1);
+        match self {
+            Transform::Identity => (row, col),
+            Transform::Rotate90 => (col, last_row - row),
+            Transform::Rotate180 => (last_row - row, last_col - col),
+            Transform::Rotate270 => (last_col - col, row),
+            Transform::FlipHorizontal => (row, last_col - col),
+            Transform::FlipVertical => (last_row - row, col),
+            Transform::FlipDiagonal => (col, row),
+            Transform::FlipAntiDiagonal => (last_col - col, last_row - row)
+        }
+    }
+}
+
+impl fmt::Display for RLE {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.name.is_empty() {
+            writeln!(f, "#N {}", self.name)?;
+        }
+        if !self.author.is_empty() {
+            writeln!(f, "#O {}", self.author)?;
+        }
+        for comment in &self.comments {
+            writeln!(f, "#C {}", comment)?;
+        }
+        if self.origin != (0, 0) {
+            writeln!(f, "#R {} {}", self.origin.1, self.origin.0)?;
+        }
+        write!(f, "x = {}, y = {}", self.width, self.height)?;
+        if let Some(rule) = &self.rule {
+            write!(f, ", rule = {}", rule)?;
+        } else if let Some(name) = &self.rule_name {
+            write!(f, ", rule = {}", name)?;
+        }
+        writeln!(f)?;
+
+        // Two-state patterns use b and o, others the multi-state alphabet
+        let multi_state = self.patterns.iter().any(|(tag, _)| matches!(tag, Tag::State(_)));
+        let mut line = String::new();
+        for (tag, count) in self.merged_patterns() {
+            let symbol = match tag {
+                Tag::DeadCell if multi_state => ".".to_owned(),
+                Tag::DeadCell => "b".to_owned(),
+                Tag::AliveCell if multi_state => "A".to_owned(),
+                Tag::AliveCell => "o".to_owned(),
+                Tag::State(state) => state_symbol(state),
+                Tag::EoL => "$".to_owned(),
+                Tag::EoF => break
+            };
+            let item = if count == 1 { symbol } else { format!("{}{}", count, symbol) };
+            if line.len() + item.len() > MAX_LINE_LENGTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            line.push_str(&item);
+        }
+        if line.len() + 1 > MAX_LINE_LENGTH {
+            writeln!(f, "{}", line)?;
+            line.clear();
+        }
+        writeln!(f, "{}!", line)
+    }
+}
+
+impl fmt::Display for ParseRleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRleError::BadHeader { line, column, text } => {
+                write!(f, "line {}, column {}: bad header near '{}'", line, column, text)
+            },
+            ParseRleError::BadRule { line, column, text, error } => {
+                write!(f, "line {}, column {}: bad rule '{}': {}", line, column, text, error)
+            },
+            ParseRleError::BadRunCount { line, column, text } => {
+                write!(f, "line {}, column {}: bad run count '{}'", line, column, text)
+            },
+            ParseRleError::UnsupportedState { line, column, text } => {
+                write!(f, "line {}, column {}: unsupported cell state '{}'", line, column, text)
+            },
+            ParseRleError::SizeMismatch { line, column, text, width, height } => {
+                write!(f, "line {}, column {}: run '{}' exceeds the header size of {}x{}", line, column, text, width, height)
+            },
+            ParseRleError::MissingEnd { line, column } => {
+                write!(f, "line {}, column {}: pattern doesn't end with '!'", line, column)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseRleError {}
+
+impl From<io::Error> for ReadRleError {
+    fn from(error: io::Error) -> ReadRleError {
+        ReadRleError::Io(error)
+    }
+}
+
+impl From<ParseRleError> for ReadRleError {
+    fn from(error: ParseRleError) -> ReadRleError {
+        ReadRleError::Parse(error)
+    }
+}
+
+impl fmt::Display for ReadRleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadRleError::Io(e) => write!(f, "{}", e),
+            ReadRleError::Parse(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl std::error::Error for ReadRleError {}
+
+fn push_pattern(patterns: &mut Vec<Pattern>, tag: Tag, count: usize) {
+    match patterns.last_mut() {
+        Some((last_tag, last_count)) if *last_tag == tag => *last_count += count,
+        _ => patterns.push((tag, count))
+    }
+}
+
+fn state_tag(state: u8) -> Tag {
+    match state {
+        0 => Tag::DeadCell,
+        1 => Tag::AliveCell,
+        _ => Tag::State(state)
+    }
+}
+
+fn tag_state(tag: Tag) -> u8 {
+    match tag {
+        Tag::AliveCell => 1,
+        Tag::State(state) => state,
+        _ => 0
+    }
+}
+
+// Multi-state cells are 'A' to 'X' for states 1 to 24, then 'pA' to 'pX' for
+// 25 to 48 and so on up to 'yO' for 255.
+fn state_number(prefix: Option<char>, letter: char) -> Option<u8> {
+    let base = match prefix {
+        Some(p) => 24 * (p as u32 - 'p' as u32 + 1),
+        None => 0
+    };
+    let state = base + letter as u32 - 'A' as u32 + 1;
+    if state > 255 {
+        return None
+    }
+    return Some(state as u8)
+}
+
+fn state_symbol(state: u8) -> String {
+    let index = state as u32 - 1;
+    let letter = char::from(b'A' + (index % 24) as u8);
+    if index < 24 {
+        return letter.to_string()
+    }
+    let prefix = char::from(b'p' + (index / 24 - 1) as u8);
+    return format!("{}{}", prefix, letter)
+}
+
+// Handles #N (name), #O (author), #C and #c (comments), #P and #R (top-left
+// corner as "x y") and #r (rule, superseded by the header).
+fn parse_comment(s: &str, line: usize, rle: &mut RLE) -> Result<(), ParseRleError> {
+    let mut chars = s.chars();
+    chars.next();
+    let kind = chars.next();
+    let text = chars.as_str().trim();
+    let column = s.chars().count() - chars.as_str().trim_start().chars().count() + 1;
+
+    match kind {
+        Some('N') => rle.name = text.to_owned(),
+        Some('O') => rle.author = text.to_owned(),
+        Some('C') | Some('c') => rle.comments.push(text.to_owned()),
+        Some('P') | Some('R') => {
+            let coords: Vec<i64> = text.split_whitespace().filter_map(|v| v.parse().ok()).collect();
+            if coords.len() == 2 {
+                rle.origin = (coords[1], coords[0]);
+            }
+        },
+        Some('r') if rle.rule.is_none() && rle.rule_name.is_none() => set_rule(rle, text, line, column)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+// Width, height and the rule text with its column
+type Header<'a> = (usize, usize, Option<(&'a str, usize)>);
+
+fn parse_header(s: &str, line: usize) -> Result<Header<'_>, ParseRleError> {
     let header_regex = Regex::new(HEADER_REGEX_STRING).unwrap();
-    let cap = header_regex.captures(s).unwrap();
-    let x = cap[1].parse::<i64>().unwrap() as usize;
-    let y = cap[2].parse::<i64>().unwrap() as usize;
-    let rule = match cap.get(3) {
-        Some(m) => m.as_str().to_owned(),
-        None => "".to_owned(),
+    let cap = match header_regex.captures(s) {
+        Some(cap) => cap,
+        None => return Err(ParseRleError::BadHeader { line, column: 1, text: s.to_owned() })
     };
-    return (x, y, rule)
-}
\ No newline at end of file
+
+    let mut size = [0usize; 2];
+    for (i, value) in size.iter_mut().enumerate() {
+        let m = cap.get(i + 1).unwrap();
+        *value = match m.as_str().parse::<usize>() {
+            Ok(v) => v,
+            Err(_) => return Err(ParseRleError::BadHeader {
+                line,
+                column: s[..m.start()].chars().count() + 1,
+                text: m.as_str().to_owned()
+            })
+        };
+    }
+
+    let rule = cap.get(3).map(|m| (m.as_str(), s[..m.start()].chars().count() + 1));
+    return Ok((size[0], size[1], rule))
+}
+
+// Generations rules like "B3/S23/3" and named rules like "LifeHistory" or
+// "WireWorld" are kept as text so their cell states can still be loaded.
+// Anything else has to be a valid life-like rule.
+fn set_rule(rle: &mut RLE, text: &str, line: usize, column: usize) -> Result<(), ParseRleError> {
+    match text.parse::<Rule>() {
+        Ok(rule) => {
+            rle.rule = Some(rule);
+            rle.rule_name = None;
+        },
+        Err(_) if is_named_rule(text) => {
+            rle.rule = None;
+            rle.rule_name = Some(text.to_owned());
+        },
+        Err(error) => return Err(ParseRleError::BadRule { line, column, text: text.to_owned(), error })
+    }
+    Ok(())
+}
+
+pub fn is_named_rule(text: &str) -> bool {
+    let starts_with_name = text.chars().next().is_some_and(|c| c.is_ascii_alphabetic() && !"BbSs".contains(c));
+    return text.matches('/').count() > 1 || (starts_with_name && !text.starts_with("MAP"))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_states(rle: &RLE) -> Vec<(i64, i64, u8)> {
+        let mut cells = rle.get_live_cell_states();
+        cells.sort_unstable();
+        return cells
+    }
+
+    const LIFE_HISTORY: &str = "#N Glider
+#C A glider with the trail it left, saved by Golly
+x = 5, y = 4, rule = LifeHistory
+2.2B$.BA.A$2B2A$3BA!
+";
+
+    const WIREWORLD: &str = "#C Diode, electrons only pass from left to right
+x = 9, y = 3, rule = WireWorld
+5.2C$BA2C.C.2C$5.2C!
+";
+
+    #[test]
+    fn loads_life_history_states() {
+        let rle: RLE = LIFE_HISTORY.parse().unwrap();
+        assert_eq!(rle.rule(), None);
+        assert_eq!(rle.rule_name(), Some("LifeHistory"));
+        assert_eq!(rle.name(), "Glider");
+        assert_eq!(sorted_states(&rle), vec![
+            (0, 2, 2), (0, 3, 2),
+            (1, 1, 2), (1, 2, 1), (1, 4, 1),
+            (2, 0, 2), (2, 1, 2), (2, 2, 1), (2, 3, 1),
+            (3, 0, 2), (3, 1, 2), (3, 2, 2), (3, 3, 1)
+        ]);
+
+        // Runs as Conway's Life with the states kept in the grid
+        let mut grid = Grid::new();
+        rle.apply(&mut grid);
+        assert_eq!(*grid.get_rule(), Rule::default());
+        assert_eq!(grid.get_state(0, 2), 2);
+        assert_eq!(grid.get_state(1, 2), 1);
+    }
+
+    #[test]
+    fn loads_wireworld_states() {
+        let rle: RLE = WIREWORLD.parse().unwrap();
+        assert_eq!(rle.rule(), None);
+        assert_eq!(rle.rule_name(), Some("WireWorld"));
+        assert_eq!(rle.comments(), ["Diode, electrons only pass from left to right"]);
+        assert_eq!(sorted_states(&rle), vec![
+            (0, 5, 3), (0, 6, 3),
+            (1, 0, 2), (1, 1, 1), (1, 2, 3), (1, 3, 3), (1, 5, 3), (1, 7, 3), (1, 8, 3),
+            (2, 5, 3), (2, 6, 3)
+        ]);
+    }
+
+    #[test]
+    fn writes_named_rules_back() {
+        for text in [LIFE_HISTORY, WIREWORLD] {
+            let rle: RLE = text.parse().unwrap();
+            let written = rle.to_string();
+            let reparsed: RLE = written.parse().unwrap();
+            assert_eq!(reparsed.rule_name(), rle.rule_name());
+            assert_eq!(sorted_states(&reparsed), sorted_states(&rle));
+        }
+    }
+
+    #[test]
+    fn keeps_generations_and_named_rules_as_text() {
+        let rle: RLE = "x = 2, y = 1, rule = B3/S23/3\nAB!".parse().unwrap();
+        assert_eq!(rle.rule_name(), Some("B3/S23/3"));
+        assert_eq!(sorted_states(&rle), vec![(0, 0, 1), (0, 1, 2)]);
+
+        let rle: RLE = "#r 23/3/3\nx = 1, y = 1\nA!".parse().unwrap();
+        assert_eq!(rle.rule_name(), Some("23/3/3"));
+
+        let rle: RLE = "x = 1, y = 1, rule = Life\no!".parse().unwrap();
+        assert_eq!(rle.rule().cloned().unwrap_or_default(), Rule::default());
+    }
+
+    #[test]
+    fn header_rule_supersedes_comment_rule() {
+        let rle: RLE = "#r WireWorld\nx = 1, y = 1, rule = B36/S23\no!".parse().unwrap();
+        assert_eq!(rle.rule_name(), None);
+        assert_eq!(rle.rule().unwrap().to_string(), "B36/S23");
+    }
+
+    #[test]
+    fn broken_life_like_rules_are_errors() {
+        let error = "x = 1, y = 1, rule = B9/S23\no!".parse::<RLE>().unwrap_err();
+        assert_eq!(error, ParseRleError::BadRule {
+            line: 1,
+            column: 22,
+            text: "B9/S23".to_owned(),
+            error: ParseRuleError::InvalidNeighborCount('9')
+        });
+    }
+
+    fn parse_error(text: &str) -> ParseRleError {
+        return text.parse::<RLE>().unwrap_err()
+    }
+
+    #[test]
+    fn reports_bad_headers() {
+        assert_eq!(parse_error("x = 3, y = three\nbo$2bo$3o!"), ParseRleError::BadHeader {
+            line: 1,
+            column: 12,
+            text: "three".to_owned()
+        });
+        assert_eq!(parse_error("#N Glider\n\nwidth 3\nbo$2bo$3o!"), ParseRleError::BadHeader {
+            line: 3,
+            column: 1,
+            text: "width 3".to_owned()
+        });
+    }
+
+    #[test]
+    fn reports_bad_run_counts() {
+        assert_eq!(parse_error("x = 3, y = 1\n0o!"), ParseRleError::BadRunCount {
+            line: 2,
+            column: 1,
+            text: "0".to_owned()
+        });
+        assert_eq!(parse_error("x = 3, y = 2\no$\nb99999999999999999999999o!"), ParseRleError::BadRunCount {
+            line: 3,
+            column: 2,
+            text: "99999999999999999999999".to_owned()
+        });
+    }
+
+    #[test]
+    fn reports_unexpected_characters() {
+        let error = parse_error("x = 3, y = 3\nbo$2bo$3z!");
+        assert_eq!(error, ParseRleError::UnsupportedState { line: 2, column: 9, text: "z".to_owned() });
+        assert_eq!(error.to_string(), "line 2, column 9: unsupported cell state 'z'");
+
+        assert_eq!(parse_error("x = 2, y = 1\nApZ!"), ParseRleError::UnsupportedState {
+            line: 2,
+            column: 2,
+            text: "pZ".to_owned()
+        });
+    }
+
+    #[test]
+    fn reports_runs_outside_the_header_size() {
+        assert_eq!(parse_error("x = 2, y = 1\nb3o!"), ParseRleError::SizeMismatch {
+            line: 2,
+            column: 2,
+            text: "3o".to_owned(),
+            width: 2,
+            height: 1
+        });
+    }
+
+    #[test]
+    fn reports_missing_end() {
+        assert_eq!(parse_error("x = 3, y = 3\nbo$2bo$\n3o\n\n"), ParseRleError::MissingEnd { line: 3, column: 3 });
+        assert_eq!(parse_error("x = 0, y = 0"), ParseRleError::MissingEnd { line: 1, column: 13 });
+    }
+
+    const GLIDER: &str = "#N Glider
+#O Richard K. Guy
+#C The smallest, most common, and first discovered spaceship.
+#C www.conwaylife.com/wiki/index.php?title=Glider
+#P -5 10
+x = 3, y = 3, rule = B3/S23
+bo$2bo$3o!
+";
+
+    #[test]
+    fn parses_comment_lines() {
+        let rle: RLE = GLIDER.parse().unwrap();
+        assert_eq!(rle.name(), "Glider");
+        assert_eq!(rle.author(), "Richard K. Guy");
+        assert_eq!(rle.comments(), [
+            "The smallest, most common, and first discovered spaceship.",
+            "www.conwaylife.com/wiki/index.php?title=Glider"
+        ]);
+        assert_eq!(rle.origin(), (10, -5));
+
+        let mut grid = Grid::new();
+        rle.apply(&mut grid);
+        let mut cells = grid.get_live_cells();
+        cells.sort_unstable();
+        assert_eq!(cells, vec![(10, -4), (11, -3), (12, -5), (12, -4), (12, -3)]);
+    }
+
+    #[test]
+    fn comment_lines_round_trip() {
+        let rle: RLE = GLIDER.parse().unwrap();
+        let reparsed: RLE = rle.to_string().parse().unwrap();
+        assert_eq!(reparsed.name(), rle.name());
+        assert_eq!(reparsed.author(), rle.author());
+        assert_eq!(reparsed.comments(), rle.comments());
+        assert_eq!(reparsed.origin(), rle.origin());
+        assert_eq!(reparsed.rule(), rle.rule());
+        assert_eq!(sorted_states(&reparsed), sorted_states(&rle));
+        assert_eq!(reparsed.to_string(), rle.to_string());
+    }
+
+    #[test]
+    fn lowercase_comments_and_r_origin() {
+        let rle: RLE = "#c first\n#C second\n#R 3 -2\nx = 1, y = 1\no!".parse().unwrap();
+        assert_eq!(rle.comments(), ["first", "second"]);
+        assert_eq!(rle.origin(), (-2, 3));
+        assert_eq!(rle.get_live_cells(), vec![(-2, 3)]);
+    }
+
+    const GOSPER_GLIDER_GUN: &str = "#N Gosper glider gun
+x = 36, y = 9, rule = B3/S23
+24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4b
+obo$10bo5bo7bo$11bo3bo$12b2o!
+";
+
+    #[test]
+    fn writes_grids_with_merged_runs() {
+        let mut grid = Grid::new();
+        for (row, col) in [(5, 5), (5, 6), (8, 7)] {
+            grid.set_cell(row, col, true);
+        }
+        grid.set_rule("B36/S23".parse().unwrap());
+        let rle = RLE::from_grid(&grid);
+        assert_eq!(rle.to_string(), "#R 5 5\nx = 3, y = 4, rule = B36/S23\n2o3$2bo!\n");
+        assert_eq!(RLE::from_grid(&Grid::new()).to_string(), "x = 0, y = 0, rule = B3/S23\n!\n");
+    }
+
+    #[test]
+    fn writes_regions_of_grids() {
+        let rle: RLE = GOSPER_GLIDER_GUN.parse().unwrap();
+        let mut grid = Grid::new();
+        rle.apply(&mut grid);
+
+        // The left block of the gun with an empty border
+        let region = RLE::from_region(&grid, (3, -1, 6, 2));
+        assert_eq!((region.width(), region.height()), (4, 4));
+        assert_eq!(region.to_string(), "#R -1 3\nx = 4, y = 4, rule = B3/S23\n$b2o$b2o!\n");
+    }
+
+    #[test]
+    fn splits_long_lines() {
+        let rle: RLE = GOSPER_GLIDER_GUN.parse().unwrap();
+        let mut grid = Grid::new();
+        rle.apply(&mut grid);
+        let mut written = RLE::from_grid(&grid);
+        written.set_name(rle.name());
+        let text = written.to_string();
+
+        // The pattern takes 80 characters, one line would be too long
+        let lines: Vec<&str> = text.lines().skip(2).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert_eq!(lines[0].len(), MAX_LINE_LENGTH);
+        assert_eq!(text, GOSPER_GLIDER_GUN);
+
+        let reparsed: RLE = text.parse().unwrap();
+        assert_eq!(sorted_states(&reparsed), sorted_states(&rle));
+    }
+
+    #[test]
+    fn writes_multi_state_cells() {
+        let cells = [(0, 0, 1), (0, 2, 2), (1, 1, 24), (1, 2, 25), (2, 0, 255)];
+        let rle = RLE::from_states(&cells, &Rule::default());
+        assert_eq!(rle.to_string(), "x = 3, y = 3, rule = B3/S23\nA.B$.XpA$yO!\n");
+        let reparsed: RLE = rle.to_string().parse().unwrap();
+        assert_eq!(sorted_states(&reparsed), cells.to_vec());
+    }
+
+    #[test]
+    fn read_into_matches_from_str() {
+        for text in [GLIDER, GOSPER_GLIDER_GUN, LIFE_HISTORY, WIREWORLD] {
+            let rle: RLE = text.parse().unwrap();
+            let mut expected = Grid::new();
+            rle.apply(&mut expected);
+
+            let mut grid = Grid::new();
+            let mut reports: Vec<ReadProgress> = Vec::new();
+            let metadata = read_into(text.as_bytes(), &mut grid, |progress| reports.push(progress)).unwrap();
+
+            let mut cells = grid.get_live_cell_states();
+            cells.sort_unstable();
+            assert_eq!(cells, sorted_states(&rle));
+            assert_eq!(cells, {
+                let mut cells = expected.get_live_cell_states();
+                cells.sort_unstable();
+                cells
+            });
+            assert_eq!(grid.get_rule(), expected.get_rule());
+            assert_eq!(metadata.name(), rle.name());
+            assert_eq!(metadata.origin(), rle.origin());
+            assert!(metadata.get_live_cells().is_empty());
+
+            let last = *reports.last().unwrap();
+            assert_eq!(last.bytes, text.len() as u64);
+            assert_eq!(last.live_cells, cells.len() as u64);
+        }
+    }
+
+    #[test]
+    fn read_into_reports_progress_and_errors() {
+        // A megabyte of dead rows, then a single cell
+        let mut text = String::from("x = 1, y = 600001\n");
+        for _ in 0..300_000 {
+            text.push_str("b$$\n");
+        }
+        text.push_str("o!\n");
+        let mut grid = Grid::new();
+        let mut reports = 0;
+        read_into(text.as_bytes(), &mut grid, |_| reports += 1).unwrap();
+        assert!(reports >= 2);
+        assert_eq!(grid.get_live_cells(), vec![(600_000, 0)]);
+
+        let error = read_into("x = 1, y = 1\n2o!".as_bytes(), &mut Grid::new(), |_| {}).unwrap_err();
+        assert!(matches!(error, ReadRleError::Parse(ParseRleError::SizeMismatch { line: 2, column: 1, .. })));
+    }
+
+    fn pasted(pattern: &str, cells: &[(i64, i64)], options: ApplyOptions) -> Vec<(i64, i64)> {
+        let mut grid = Grid::new();
+        for (row, col) in cells {
+            grid.set_cell(*row, *col, true);
+        }
+        pattern.parse::<RLE>().unwrap().apply_with(&mut grid, &options);
+        let mut cells = grid.get_live_cells();
+        cells.sort_unstable();
+        return cells
+    }
+
+    fn at_origin(transform: Transform) -> ApplyOptions {
+        return ApplyOptions { placement: Placement::At(0, 0), transform, ..ApplyOptions::default() }
+    }
+
+    #[test]
+    fn transforms_pasted_patterns() {
+        // ooo
+        // o..
+        let pattern = "x = 3, y = 2\n3o$o!";
+        let cases = [
+            (Transform::Identity, vec![(0, 0), (0, 1), (0, 2), (1, 0)]),
+            (Transform::Rotate90, vec![(0, 0), (0, 1), (1, 1), (2, 1)]),
+            (Transform::Rotate180, vec![(0, 2), (1, 0), (1, 1), (1, 2)]),
+            (Transform::Rotate270, vec![(0, 0), (1, 0), (2, 0), (2, 1)]),
+            (Transform::FlipHorizontal, vec![(0, 0), (0, 1), (0, 2), (1, 2)]),
+            (Transform::FlipVertical, vec![(0, 0), (1, 0), (1, 1), (1, 2)]),
+            (Transform::FlipDiagonal, vec![(0, 0), (0, 1), (1, 0), (2, 0)]),
+            (Transform::FlipAntiDiagonal, vec![(0, 1), (1, 1), (2, 0), (2, 1)])
+        ];
+        for (transform, expected) in cases.iter() {
+            assert_eq!(pasted(pattern, &[], at_origin(*transform)), *expected, "{:?}", transform);
+            let (height, width) = transform.size(2, 3);
+            assert!(expected.iter().all(|(row, col)| *row < height as i64 && *col < width as i64), "{:?}", transform);
+        }
+    }
+
+    #[test]
+    fn places_pasted_patterns() {
+        let pattern = "#P 5 -3\nx = 3, y = 2\n3o$o!";
+        let options = |placement| ApplyOptions { placement, ..ApplyOptions::default() };
+        assert_eq!(pasted(pattern, &[], options(Placement::Origin)), vec![(-3, 5), (-3, 6), (-3, 7), (-2, 5)]);
+        assert_eq!(pasted(pattern, &[], options(Placement::At(10, 20))), vec![(10, 20), (10, 21), (10, 22), (11, 20)]);
+        assert_eq!(pasted(pattern, &[], options(Placement::CenteredOn(10, 20))), vec![(9, 19), (9, 20), (9, 21), (10, 19)]);
+
+        // Centering uses the size after the transformation
+        let rotated = ApplyOptions { placement: Placement::CenteredOn(10, 20), transform: Transform::Rotate90, ..ApplyOptions::default() };
+        assert_eq!(pasted(pattern, &[], rotated), vec![(9, 19), (9, 20), (10, 20), (11, 20)]);
+    }
+
+    #[test]
+    fn combines_pasted_cells_by_mode() {
+        // o.
+        // .o
+        let pattern = "x = 2, y = 2\no$bo!";
+        let grid = [(0, 0), (0, 1), (5, 5)];
+        let options = |mode| ApplyOptions { placement: Placement::At(0, 0), mode, ..ApplyOptions::default() };
+        assert_eq!(pasted(pattern, &grid, options(PasteMode::Copy)), vec![(0, 0), (1, 1), (5, 5)]);
+        assert_eq!(pasted(pattern, &grid, options(PasteMode::Or)), vec![(0, 0), (0, 1), (1, 1), (5, 5)]);
+        assert_eq!(pasted(pattern, &grid, options(PasteMode::And)), vec![(0, 0), (5, 5)]);
+        assert_eq!(pasted(pattern, &grid, options(PasteMode::Xor)), vec![(0, 1), (1, 1), (5, 5)]);
+    }
+
+    #[test]
+    fn adopts_the_rule_and_states_when_asked() {
+        let rle: RLE = "x = 2, y = 1, rule = B36/S23\nAB!".parse().unwrap();
+        let mut grid = Grid::new();
+        grid.set_rule("B2/S".parse().unwrap());
+        rle.apply_with(&mut grid, &ApplyOptions { adopt_rule: false, ..ApplyOptions::default() });
+        assert_eq!(grid.get_rule().to_string(), "B2/S");
+        assert_eq!((grid.get_state(0, 0), grid.get_state(0, 1)), (1, 2));
+
+        rle.apply_with(&mut grid, &ApplyOptions::default());
+        assert_eq!(grid.get_rule().to_string(), "B36/S23");
+    }
+}
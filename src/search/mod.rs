@@ -0,0 +1,2 @@
+pub mod predecessor;
+pub mod sat;
\ No newline at end of file
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::cli;
+use crate::grid::{Grid, Rule, NEIGHBORHOOD_COUNT};
+use crate::rle::RLE;
+use crate::search::sat::{Lit, SatResult, Solver};
+
+pub enum Predecessor {
+    Found(RLE),
+    // Proven: no parent has all its live cells inside the searched region
+    NoneWithinBounds
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PredecessorOptions {
+    pub pattern: String,
+    pub rule: Option<Rule>,
+    pub margin: usize,
+    pub conflict_limit: Option<u64>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchError {
+    // The rule turns empty space alive, so no finite pattern has a parent
+    UnboundedRule,
+    ConflictLimitReached(u64)
+}
+
+pub fn run_command(options: &PredecessorOptions) -> Result<Predecessor, String> {
+    let pattern = cli::load_pattern(&options.pattern)?;
+    let mut grid = Grid::new();
+    pattern.apply(&mut grid);
+    if let Some(rule) = options.rule.clone() {
+        grid.set_rule(rule);
+    }
+
+    let mut result = find_predecessor(&grid, options.margin, options.conflict_limit).map_err(|e| e.to_string())?;
+    if let (Predecessor::Found(parent), false) = (&mut result, pattern.name().is_empty()) {
+        parent.set_name(&format!("Predecessor of {}", pattern.name()));
+    }
+    return Ok(result)
+}
+
+// Looks for a pattern that becomes the current contents of the grid after one
+// generation under the grid's rule. The parent may only have live cells in the
+// bounding box of the grid expanded by margin cells on every side.
+pub fn find_predecessor(grid: &Grid, margin: usize, conflict_limit: Option<u64>) -> Result<Predecessor, SearchError> {
+    let rule = grid.get_rule();
+    let table = rule.table();
+    if table[0] {
+        return Err(SearchError::UnboundedRule)
+    }
+
+    let (min_row, min_col, max_row, max_col) = match grid.get_bounding_box() {
+        Some(bounds) => bounds,
+        None => return Ok(Predecessor::Found(RLE::from_cells(&[], rule)))
+    };
+    let margin = margin as i64;
+    let top = min_row - margin;
+    let left = min_col - margin;
+    let bottom = max_row + margin;
+    let right = max_col + margin;
+
+    let mut solver = Solver::new();
+    let mut vars: HashMap<(i64, i64), usize> = HashMap::new();
+    for row in top..=bottom {
+        for col in left..=right {
+            vars.insert((row, col), solver.new_var());
+        }
+    }
+
+    // Cells one step outside the region can still be born from cells inside it
+    for row in (top - 1)..=(bottom + 1) {
+        for col in (left - 1)..=(right + 1) {
+            let target = grid.get_cell(row, col);
+            let mut neighborhood_vars: Vec<(usize, usize)> = Vec::new();
+            let mut bit = NEIGHBORHOOD_COUNT >> 1;
+            for neighbor_row in -1..2 {
+                for neighbor_col in -1..2 {
+                    if let Some(var) = vars.get(&(row + neighbor_row, col + neighbor_col)) {
+                        neighborhood_vars.push((*var, bit));
+                    }
+                    bit >>= 1;
+                }
+            }
+
+            forbid_wrong_neighborhoods(&mut solver, &table[..], &neighborhood_vars, target);
+        }
+    }
+
+    match solver.solve(conflict_limit) {
+        SatResult::Satisfiable(model) => {
+            let cells: Vec<(i64, i64)> = vars.iter()
+                .filter(|(_, var)| model[**var])
+                .map(|(cell, _)| *cell)
+                .collect();
+            Ok(Predecessor::Found(RLE::from_cells(&cells, rule)))
+        },
+        SatResult::Unsatisfiable => Ok(Predecessor::NoneWithinBounds),
+        SatResult::Unknown => Err(SearchError::ConflictLimitReached(conflict_limit.unwrap_or(0)))
+    }
+}
+
+// Adds one clause per assignment of the free neighbors that would not produce
+// the target state. Neighbors outside the region are dead.
+fn forbid_wrong_neighborhoods(solver: &mut Solver, table: &[bool], neighborhood_vars: &[(usize, usize)], target: bool) {
+    for assignment in 0..(1usize << neighborhood_vars.len()) {
+        let mut neighborhood = 0;
+        for (i, (_, bit)) in neighborhood_vars.iter().enumerate() {
+            if assignment & (1 << i) != 0 {
+                neighborhood |= bit;
+            }
+        }
+        if table[neighborhood] == target {
+            continue;
+        }
+
+        let clause: Vec<Lit> = neighborhood_vars.iter().enumerate()
+            .map(|(i, (var, _))| Lit::new(*var, assignment & (1 << i) == 0))
+            .collect();
+        solver.add_clause(&clause);
+    }
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::UnboundedRule => write!(f, "rules with B0 have no finite predecessors"),
+            SearchError::ConflictLimitReached(n) => write!(f, "search gave up after {} conflicts", n)
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid_of(rle: &str) -> Grid {
+        let mut grid = Grid::new();
+        rle.parse::<RLE>().unwrap().apply(&mut grid);
+        return grid
+    }
+
+    fn sorted_cells(grid: &Grid) -> Vec<(i64, i64)> {
+        let mut cells = grid.get_live_cells();
+        cells.sort_unstable();
+        return cells
+    }
+
+    #[test]
+    fn glider_predecessor_steps_into_glider() {
+        let glider = grid_of("x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!");
+        let parent = match find_predecessor(&glider, 1, None) {
+            Ok(Predecessor::Found(parent)) => parent,
+            _ => panic!("glider has a predecessor")
+        };
+
+        let mut grid = Grid::new();
+        parent.apply(&mut grid);
+        grid.calc_next_generation();
+        assert_eq!(sorted_cells(&grid), sorted_cells(&glider));
+    }
+
+    #[test]
+    fn single_cell_has_no_predecessor_without_margin() {
+        let cell = grid_of("x = 1, y = 1, rule = B3/S23\no!");
+        assert!(matches!(find_predecessor(&cell, 0, None), Ok(Predecessor::NoneWithinBounds)));
+    }
+
+    #[test]
+    fn empty_grid_is_its_own_predecessor() {
+        let parent = match find_predecessor(&Grid::new(), 2, None) {
+            Ok(Predecessor::Found(parent)) => parent,
+            _ => panic!("empty grid has a predecessor")
+        };
+        assert!(parent.get_live_cells().is_empty());
+    }
+
+    #[test]
+    fn b0_rules_are_rejected() {
+        let block = grid_of("x = 2, y = 2, rule = B03/S23\n2o$2o!");
+        assert_eq!(find_predecessor(&block, 1, None).err(), Some(SearchError::UnboundedRule));
+    }
+}
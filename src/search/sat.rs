@@ -0,0 +1,472 @@
+use std::ops::Not;
+
+// A conflict driven clause learning solver. It learns 1-UIP clauses, picks
+// branching variables by activity, saves phases and restarts geometrically.
+// Learnt clauses are never deleted, which is fine for the problem sizes
+// produced by the searches in this module.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Lit(usize);
+
+impl Lit {
+    pub fn positive(var: usize) -> Lit {
+        Lit(var * 2)
+    }
+
+    pub fn negative(var: usize) -> Lit {
+        Lit(var * 2 + 1)
+    }
+
+    pub fn new(var: usize, value: bool) -> Lit {
+        if value { Lit::positive(var) } else { Lit::negative(var) }
+    }
+
+    pub fn var(self) -> usize {
+        self.0 / 2
+    }
+
+    fn is_negative(self) -> bool {
+        self.0 & 1 == 1
+    }
+
+    fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SatResult {
+    Satisfiable(Vec<bool>),
+    Unsatisfiable,
+    Unknown
+}
+
+const RESTART_FIRST: u64 = 100;
+const RESTART_GROWTH: f64 = 1.5;
+const ACTIVITY_DECAY: f64 = 0.95;
+
+pub struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    units: Vec<Lit>,
+    trivially_unsat: bool,
+    // Clauses watching each literal, visited when that literal becomes false
+    watches: Vec<Vec<usize>>,
+    assigns: Vec<Option<bool>>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    phase: Vec<bool>,
+    activity: Vec<f64>,
+    activity_inc: f64,
+    seen: Vec<bool>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    qhead: usize
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver {
+            clauses: Vec::new(),
+            units: Vec::new(),
+            trivially_unsat: false,
+            watches: Vec::new(),
+            assigns: Vec::new(),
+            level: Vec::new(),
+            reason: Vec::new(),
+            phase: Vec::new(),
+            activity: Vec::new(),
+            activity_inc: 1.0,
+            seen: Vec::new(),
+            trail: Vec::new(),
+            trail_lim: Vec::new(),
+            qhead: 0
+        }
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        let var = self.assigns.len();
+        self.assigns.push(None);
+        self.level.push(0);
+        self.reason.push(None);
+        self.phase.push(false);
+        self.activity.push(0.0);
+        self.seen.push(false);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        return var
+    }
+
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        let mut clause = lits.to_vec();
+        clause.sort_unstable_by_key(|l| l.index());
+        clause.dedup();
+        if clause.windows(2).any(|w| w[0] == !w[1]) {
+            return
+        }
+
+        match clause.len() {
+            0 => self.trivially_unsat = true,
+            1 => self.units.push(clause[0]),
+            _ => {
+                self.add_watched_clause(clause);
+            }
+        }
+    }
+
+    // Stops with SatResult::Unknown after conflict_limit conflicts, if given.
+    pub fn solve(&mut self, conflict_limit: Option<u64>) -> SatResult {
+        if self.trivially_unsat {
+            return SatResult::Unsatisfiable
+        }
+
+        self.backtrack(0);
+        self.trail.clear();
+        self.qhead = 0;
+        for assign in self.assigns.iter_mut() {
+            *assign = None;
+        }
+        for i in 0..self.units.len() {
+            let unit = self.units[i];
+            match self.value(unit) {
+                Some(false) => return SatResult::Unsatisfiable,
+                Some(true) => {},
+                None => self.enqueue(unit, None)
+            }
+        }
+
+        let mut conflicts: u64 = 0;
+        let mut restart_limit = RESTART_FIRST as f64;
+        let mut conflicts_since_restart: u64 = 0;
+
+        loop {
+            if let Some(conflict) = self.propagate() {
+                conflicts += 1;
+                conflicts_since_restart += 1;
+                if self.trail_lim.is_empty() {
+                    return SatResult::Unsatisfiable
+                }
+
+                let (learnt, backtrack_level) = self.analyze(conflict);
+                self.backtrack(backtrack_level);
+                if learnt.len() == 1 {
+                    self.enqueue(learnt[0], None);
+                } else {
+                    let asserting = learnt[0];
+                    let index = self.add_watched_clause(learnt);
+                    self.enqueue(asserting, Some(index));
+                }
+                self.activity_inc /= ACTIVITY_DECAY;
+
+                if conflict_limit.is_some_and(|limit| conflicts >= limit) {
+                    return SatResult::Unknown
+                }
+                if conflicts_since_restart as f64 >= restart_limit {
+                    conflicts_since_restart = 0;
+                    restart_limit *= RESTART_GROWTH;
+                    self.backtrack(0);
+                }
+                continue;
+            }
+
+            match self.pick_branch_var() {
+                Some(var) => {
+                    self.trail_lim.push(self.trail.len());
+                    let lit = Lit::new(var, self.phase[var]);
+                    self.enqueue(lit, None);
+                },
+                None => {
+                    let model = self.assigns.iter().map(|a| a.unwrap_or(false)).collect();
+                    return SatResult::Satisfiable(model)
+                }
+            }
+        }
+    }
+
+    fn add_watched_clause(&mut self, clause: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[clause[0].index()].push(index);
+        self.watches[clause[1].index()].push(index);
+        self.clauses.push(clause);
+        return index
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.assigns[lit.var()].map(|v| v != lit.is_negative())
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.assigns[var] = Some(!lit.is_negative());
+        self.level[var] = self.trail_lim.len();
+        self.reason[var] = reason;
+        self.trail.push(lit);
+    }
+
+    // Returns the index of a conflicting clause, if any.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.qhead < self.trail.len() {
+            let false_lit = !self.trail[self.qhead];
+            self.qhead += 1;
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut conflict = None;
+            let mut i = 0;
+            let mut j = 0;
+
+            while i < watchers.len() {
+                let index = watchers[i];
+                i += 1;
+
+                if self.clauses[index][0] == false_lit {
+                    self.clauses[index].swap(0, 1);
+                }
+                let first = self.clauses[index][0];
+                if self.value(first) == Some(true) {
+                    watchers[j] = index;
+                    j += 1;
+                    continue;
+                }
+
+                let mut moved = false;
+                for k in 2..self.clauses[index].len() {
+                    if self.value(self.clauses[index][k]) != Some(false) {
+                        self.clauses[index].swap(1, k);
+                        let new_watch = self.clauses[index][1];
+                        self.watches[new_watch.index()].push(index);
+                        moved = true;
+                        break;
+                    }
+                }
+                if moved {
+                    continue;
+                }
+
+                watchers[j] = index;
+                j += 1;
+                if self.value(first) == Some(false) {
+                    while i < watchers.len() {
+                        watchers[j] = watchers[i];
+                        i += 1;
+                        j += 1;
+                    }
+                    conflict = Some(index);
+                } else {
+                    self.enqueue(first, Some(index));
+                }
+            }
+
+            watchers.truncate(j);
+            self.watches[false_lit.index()] = watchers;
+            if conflict.is_some() {
+                self.qhead = self.trail.len();
+                return conflict
+            }
+        }
+        return None
+    }
+
+    // Derives the first unique implication point clause of a conflict and the
+    // level to backtrack to. The asserting literal is placed first.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let current_level = self.trail_lim.len();
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut implied: Option<Lit> = None;
+        let mut index = self.trail.len();
+
+        loop {
+            let skip = if implied.is_some() { 1 } else { 0 };
+            for k in skip..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var();
+                if self.seen[var] || self.level[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump_activity(var);
+                if self.level[var] == current_level {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[lit.var()] = false;
+            implied = Some(lit);
+            pending -= 1;
+            if pending == 0 {
+                break;
+            }
+            clause = self.reason[lit.var()].unwrap();
+        }
+
+        learnt[0] = !implied.unwrap();
+        for lit in &learnt[1..] {
+            self.seen[lit.var()] = false;
+        }
+
+        let mut backtrack_level = 0;
+        for k in 1..learnt.len() {
+            let level = self.level[learnt[k].var()];
+            if level > backtrack_level {
+                backtrack_level = level;
+                learnt.swap(1, k);
+            }
+        }
+        return (learnt, backtrack_level)
+    }
+
+    fn bump_activity(&mut self, var: usize) {
+        self.activity[var] += self.activity_inc;
+        if self.activity[var] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.activity_inc *= 1e-100;
+        }
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.trail_lim.len() <= level {
+            return
+        }
+        let start = self.trail_lim[level];
+        for lit in self.trail.drain(start..) {
+            let var = lit.var();
+            self.phase[var] = !lit.is_negative();
+            self.assigns[var] = None;
+            self.reason[var] = None;
+        }
+        self.trail_lim.truncate(level);
+        self.qhead = self.trail.len();
+    }
+
+    fn pick_branch_var(&self) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for var in 0..self.assigns.len() {
+            if self.assigns[var].is_some() {
+                continue;
+            }
+            if best.is_none_or(|b| self.activity[var] > self.activity[b]) {
+                best = Some(var);
+            }
+        }
+        return best
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        return Solver::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(var_count: usize, clauses: &[Vec<i32>]) -> (SatResult, Vec<Vec<Lit>>) {
+        let mut solver = Solver::new();
+        for _ in 0..var_count {
+            solver.new_var();
+        }
+        // DIMACS style: 1-based variables, negative numbers are negated literals
+        let clauses: Vec<Vec<Lit>> = clauses.iter()
+            .map(|c| c.iter().map(|l| Lit::new(l.unsigned_abs() as usize - 1, *l > 0)).collect())
+            .collect();
+        for clause in &clauses {
+            solver.add_clause(clause);
+        }
+        return (solver.solve(None), clauses)
+    }
+
+    fn satisfies(model: &[bool], clauses: &[Vec<Lit>]) -> bool {
+        return clauses.iter().all(|c| c.iter().any(|l| model[l.var()] != l.is_negative()))
+    }
+
+    #[test]
+    fn finds_model_of_satisfiable_formula() {
+        let formula = vec![vec![1, 2], vec![-1, 3], vec![-2, -3], vec![-3, 4], vec![-4, -1, 2]];
+        match solve(4, &formula) {
+            (SatResult::Satisfiable(model), clauses) => assert!(satisfies(&model, &clauses)),
+            (result, _) => panic!("expected a model, got {:?}", result)
+        }
+    }
+
+    #[test]
+    fn unit_clauses_fix_variables() {
+        match solve(3, &[vec![1], vec![-2], vec![-1, 2, 3]]) {
+            (SatResult::Satisfiable(model), _) => assert_eq!(model, vec![true, false, true]),
+            (result, _) => panic!("expected a model, got {:?}", result)
+        }
+    }
+
+    #[test]
+    fn contradicting_units_are_unsatisfiable() {
+        assert_eq!(solve(1, &[vec![1], vec![-1]]).0, SatResult::Unsatisfiable);
+    }
+
+    #[test]
+    fn pigeonhole_is_unsatisfiable() {
+        // Four pigeons in three holes, variable 3 * pigeon + hole + 1
+        let var = |pigeon: i32, hole: i32| 3 * pigeon + hole + 1;
+        let mut formula: Vec<Vec<i32>> = (0..4).map(|p| (0..3).map(|h| var(p, h)).collect()).collect();
+        for hole in 0..3 {
+            for a in 0..4 {
+                for b in (a + 1)..4 {
+                    formula.push(vec![-var(a, hole), -var(b, hole)]);
+                }
+            }
+        }
+        assert_eq!(solve(12, &formula).0, SatResult::Unsatisfiable);
+    }
+
+    #[test]
+    fn agrees_with_brute_force_on_random_formulas() {
+        let mut seed: u64 = 0x9e3779b97f4a7c15;
+        let mut next = |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+
+        for _ in 0..300 {
+            let var_count = 3 + next(6) as usize;
+            let clause_count = var_count * 4 + next(4) as usize;
+            let formula: Vec<Vec<i32>> = (0..clause_count)
+                .map(|_| (0..3).map(|_| {
+                    let var = next(var_count as u64) as i32 + 1;
+                    if next(2) == 0 { var } else { -var }
+                }).collect())
+                .collect();
+
+            let (result, clauses) = solve(var_count, &formula);
+            let brute_force = (0..1u32 << var_count).any(|bits| {
+                let model: Vec<bool> = (0..var_count).map(|v| bits & (1 << v) != 0).collect();
+                satisfies(&model, &clauses)
+            });
+            match result {
+                SatResult::Satisfiable(model) => assert!(satisfies(&model, &clauses)),
+                SatResult::Unsatisfiable => assert!(!brute_force, "{:?} is satisfiable", formula),
+                SatResult::Unknown => panic!("no conflict limit was set")
+            }
+        }
+    }
+}